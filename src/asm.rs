@@ -0,0 +1,198 @@
+use crate::{
+    interop,
+    opcode::{self, Operand, SYSCALL},
+};
+
+/// Assemble script from text with one instruction per line, e.g.:
+///
+/// ```text
+/// PUSHINT8 -5        # integers are encoded as little-endian two's complement
+/// PUSHDATA1 0x0102   # hex operands are copied as is
+/// PUSHDATA1 "hello"  # strings are UTF-8 encoded
+/// TRY 3 5            # several integers split the operand evenly
+/// SYSCALL System.Runtime.Log  # interop names are hashed
+/// MODMUL
+/// ```
+///
+/// Comments start with `#` or `;` outside of quoted strings. Errors contain line number.
+pub fn assemble(text: &str) -> Result<Vec<u8>, String> {
+    let mut script = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        assemble_line(line, &mut script).map_err(|err| format!("line {}: {err}", i + 1))?;
+    }
+    Ok(script)
+}
+
+/// Line without its comment, `#` and `;` inside quoted strings don't start one.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn assemble_line(line: &str, script: &mut Vec<u8>) -> Result<(), String> {
+    let (name, operand) = match line.split_once(char::is_whitespace) {
+        Some((name, operand)) => (name, operand.trim()),
+        None => (line, ""),
+    };
+    let info = opcode::by_name(name).ok_or(format!("unknown opcode `{name}`"))?;
+    script.push(info.code);
+    match info.operand {
+        Operand::None if operand.is_empty() => Ok(()),
+        Operand::None => Err(format!("{} has no operand", info.name)),
+        Operand::Fixed(_) if info.code == SYSCALL && interop::by_name(operand).is_some() => {
+            script.extend(interop::by_name(operand).unwrap().hash());
+            Ok(())
+        }
+        Operand::Fixed(size) => {
+            script.extend(fixed_operand(operand, size)?);
+            Ok(())
+        }
+        Operand::Prefix(prefix) => {
+            let data = data_operand(operand)?;
+            if prefix < 8 && data.len() >= 1 << (prefix * 8) {
+                return Err(format!("data is too long for {}", info.name));
+            }
+            script.extend(&data.len().to_le_bytes()[..prefix]);
+            script.extend(data);
+            Ok(())
+        }
+    }
+}
+
+fn fixed_operand(operand: &str, size: usize) -> Result<Vec<u8>, String> {
+    if let Some(hex) = operand.strip_prefix("0x") {
+        let bytes = parse_hex(hex)?;
+        if bytes.len() != size {
+            return Err(format!(
+                "expected {size} operand bytes, got {}",
+                bytes.len()
+            ));
+        }
+        return Ok(bytes);
+    }
+    let values: Vec<&str> = operand.split_whitespace().collect();
+    if values.is_empty() || !size.is_multiple_of(values.len()) {
+        return Err(format!("expected operand of {size} bytes"));
+    }
+    let part = size / values.len();
+    let mut bytes = Vec::with_capacity(size);
+    for value in values {
+        let value: i128 = value
+            .parse()
+            .map_err(|_| format!("invalid integer `{value}`"))?;
+        // Allow both signed (offsets) and unsigned (slot counts, types) values.
+        if part < 16 && !(-(1i128 << (part * 8 - 1))..1i128 << (part * 8)).contains(&value) {
+            return Err(format!("integer `{value}` does not fit in {part} bytes"));
+        }
+        let encoded = value.to_le_bytes();
+        let sign = if value < 0 { 0xff } else { 0x00 };
+        bytes.extend((0..part).map(|i| encoded.get(i).copied().unwrap_or(sign)));
+    }
+    Ok(bytes)
+}
+
+fn data_operand(operand: &str) -> Result<Vec<u8>, String> {
+    if let Some(hex) = operand.strip_prefix("0x") {
+        parse_hex(hex)
+    } else if operand.len() >= 2 && operand.starts_with('"') && operand.ends_with('"') {
+        Ok(operand.as_bytes()[1..operand.len() - 1].to_vec())
+    } else if operand.is_empty() {
+        Ok(Vec::new())
+    } else {
+        Err(format!(
+            "invalid data `{operand}`, expected 0x<hex> or \"string\""
+        ))
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in `{hex}`"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("invalid hex `{hex}`")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::*;
+
+    /// Opcodes and operands of assembled script.
+    fn decode(script: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let decoded: Vec<_> = instructions(script)
+            .map(|instr| (script[instr.offset], instr.operand.to_vec()))
+            .collect();
+        assert_eq!(
+            decoded
+                .iter()
+                .map(|(_, operand)| 1 + operand.len())
+                .sum::<usize>(),
+            script.len()
+        );
+        decoded
+    }
+
+    #[test]
+    fn round_trip() {
+        let script = assemble(
+            "PUSHINT8 -5\n\
+             PUSHINT16 0x0102 # hex\n\
+             PUSHDATA1 \"hello\" ; string\n\
+             TRY 3 -5\n\
+             SYSCALL System.Runtime.Log\n\
+             \n\
+             MODMUL",
+        )
+        .unwrap();
+        assert_eq!(
+            decode(&script),
+            vec![
+                (PUSHINT8, vec![0xfb]),
+                (PUSHINT16, vec![0x01, 0x02]),
+                (PUSHDATA1, [&[5u8][..], b"hello"].concat()),
+                (TRY, vec![3, 0xfb]),
+                (SYSCALL, vec![0xcf, 0xe7, 0x47, 0x96]),
+                (MODMUL, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment_chars_in_strings() {
+        let script =
+            assemble("PUSHDATA1 \"a;b#c\" # comment \"quoted\"\nPUSHDATA1 \"\";x").unwrap();
+        assert_eq!(
+            decode(&script),
+            vec![
+                (PUSHDATA1, [&[5u8][..], b"a;b#c"].concat()),
+                (PUSHDATA1, vec![0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            assemble("NOP\nFOO").unwrap_err(),
+            "line 2: unknown opcode `FOO`"
+        );
+        assert!(assemble("PUSHINT8 256").is_err());
+        assert!(assemble("PUSHINT16 0x01").is_err());
+        assert!(assemble("RET 1").is_err());
+        assert!(assemble("PUSHDATA1 \"unterminated").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Create new campaign directory named after current time (UTC) inside output directory.
+pub fn create(out: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(out)?;
+    let name = timestamp(SystemTime::now());
+    let mut dir = out.join(&name);
+    let mut ctr = 2;
+    while dir.exists() {
+        dir = out.join(format!("{name}-{ctr}"));
+        ctr += 1;
+    }
+    fs::create_dir(dir.as_path())?;
+    Ok(dir)
+}
+
+/// Find the most recent campaign directory inside output directory.
+pub fn latest(out: &Path) -> Option<PathBuf> {
+    fs::read_dir(out)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join("config.json").exists())
+        .max_by_key(|path| path.metadata().and_then(|m| m.modified()).ok())
+}
+
+/// Format time as `YYYY-MM-DD_HH-MM-SS`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    // Convert days since epoch to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn timestamp_format() {
+        assert_eq!(timestamp(at(0)), "1970-01-01_00-00-00");
+        assert_eq!(timestamp(at(951_782_400)), "2000-02-29_00-00-00");
+        assert_eq!(timestamp(at(1_733_486_399)), "2024-12-06_11-59-59");
+    }
+
+    #[test]
+    fn timestamps_sort_by_time() {
+        let times = [0, 59, 3_600, 86_399, 86_400, 951_782_400, 1_733_486_399];
+        let names: Vec<String> = times.iter().map(|secs| timestamp(at(*secs))).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn create_and_find_latest() {
+        let out = std::env::temp_dir().join(format!("n3on-diff-campaign-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out);
+        assert_eq!(latest(&out), None);
+        let fst = create(&out).unwrap();
+        // Directory without config is not a campaign.
+        assert_eq!(latest(&out), None);
+        fs::write(fst.join("config.json"), "{}").unwrap();
+        assert_eq!(latest(&out), Some(fst.clone()));
+        // Campaign started in the same second gets a suffix.
+        let snd = create(&out).unwrap();
+        assert_ne!(fst, snd);
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(snd.join("config.json"), "{}").unwrap();
+        assert_eq!(latest(&out), Some(snd));
+        fs::remove_dir_all(&out).unwrap();
+    }
+}
@@ -0,0 +1,656 @@
+use std::{fs, path::PathBuf};
+
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgAction, ArgMatches, CommandFactory, FromArgMatches,
+    Parser, Subcommand,
+};
+use libafl_bolts::{
+    core_affinity::{CoreId, Cores},
+    current_nanos, hash_std,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    campaign,
+    feedback::FeedbackKind,
+    interop::{self, Syscall},
+    limits::Limits,
+    observer::GoCoverFilter,
+    opcode,
+};
+
+/// Options recorded in `config.json` that a resumed campaign keeps, with ids of the arguments setting them.
+const RECORDED_OPTIONS: &[(&str, &[&str])] = &[
+    (
+        "detect_status_diff",
+        &["DETECT_STATUS_DIFFERENCE", "NO_DETECT_STATUS_DIFFERENCE"],
+    ),
+    (
+        "detect_crash_diff",
+        &["DETECT_CRASH_DIFFERENCE", "NO_DETECT_CRASH_DIFFERENCE"],
+    ),
+    ("spec_oracle", &["SPEC_ORACLE", "NO_SPEC_ORACLE"]),
+    (
+        "read_corpus_from_file",
+        &["READ_CORPUS_FROM_FILE", "NO_READ_CORPUS_FROM_FILE"],
+    ),
+    ("corpus", &["CORPUS"]),
+    ("spread_corpus", &["SPREAD_CORPUS", "NO_SPREAD_CORPUS"]),
+    ("generate_seeds", &["GENERATE_SEEDS"]),
+    ("target_opcodes", &["TARGET_OPCODES"]),
+    ("max_callees", &["MAX_CALLEES"]),
+    ("max_args", &["MAX_ARGS"]),
+    ("syscalls", &["SYSCALLS"]),
+    ("limits", &["LIMITS"]),
+    ("fuzz_limits", &["FUZZ_LIMITS"]),
+    ("compare_aliasing", &["COMPARE_ALIASING"]),
+    (
+        "go_cover_filter",
+        &[
+            "GO_COVER_FILTER",
+            "GO_COVER_PKG",
+            "GO_COVER_EXCLUDE_PKG",
+            "GO_COVER_FILE",
+            "GO_COVER_EXCLUDE_FILE",
+        ],
+    ),
+    ("seed", &["SEED"]),
+];
+
+/// Packages of neo-go VM and helpers it uses for big integers.
+const DEFAULT_GO_COVER_PACKAGES: &[&str] = &[
+    "github.com/nspcc-dev/neo-go/pkg/vm/...",
+    "github.com/nspcc-dev/neo-go/pkg/encoding/bigint",
+];
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(
+        long,
+        help = "Report error if VMs execution ends with different status. Can result in many false positives!",
+        name = "DETECT_STATUS_DIFFERENCE",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        overrides_with = "NO_DETECT_STATUS_DIFFERENCE"
+    )]
+    detect_status_diff: bool,
+
+    #[arg(
+        long,
+        help = "Don't report error if VMs execution ends with different status.",
+        name = "NO_DETECT_STATUS_DIFFERENCE",
+        overrides_with = "DETECT_STATUS_DIFFERENCE"
+    )]
+    no_detect_status_diff: bool,
+
+    #[arg(
+        long,
+        help = "Report error if output of any VM violates NeoVM spec (integer and item size bounds, canonical booleans, last opcode consistent with status), even if both VMs agree",
+        name = "SPEC_ORACLE",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        overrides_with = "NO_SPEC_ORACLE"
+    )]
+    spec_oracle: bool,
+
+    #[arg(
+        long,
+        help = "Don't check outputs of VMs against NeoVM spec.",
+        name = "NO_SPEC_ORACLE",
+        overrides_with = "SPEC_ORACLE"
+    )]
+    no_spec_oracle: bool,
+
+    #[arg(
+        short = 'c',
+        long,
+        help = "Report error if one of VMs crashes.",
+        name = "DETECT_CRASH_DIFFERENCE",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = true,
+        default_missing_value = "true",
+        overrides_with = "NO_DETECT_CRASH_DIFFERENCE"
+    )]
+    detect_crash_diff: bool,
+
+    #[arg(
+        long,
+        help = "Don't report error if one of VMs crashes.",
+        name = "NO_DETECT_CRASH_DIFFERENCE",
+        overrides_with = "DETECT_CRASH_DIFFERENCE"
+    )]
+    no_detect_crash_diff: bool,
+
+    #[arg(
+        short = 'f',
+        long,
+        help = "Read initial corpus from file",
+        name = "READ_CORPUS_FROM_FILE",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = true,
+        default_missing_value = "true",
+        overrides_with = "NO_READ_CORPUS_FROM_FILE"
+    )]
+    read_corpus_from_file: bool,
+
+    #[arg(
+        long,
+        help = "Don't read initial corpus from file",
+        name = "NO_READ_CORPUS_FROM_FILE",
+        overrides_with = "READ_CORPUS_FROM_FILE"
+    )]
+    no_read_corpus_from_file: bool,
+
+    #[arg(
+        long,
+        help = "Initial corpus files or directories: base64 script per line, raw scripts (*.bin), NEF files (*.nef, with methods from *.manifest.json next to them) or assembler text (*.asm)",
+        name = "CORPUS",
+        num_args = 1..,
+        default_value = "./corpus/corpus.txt"
+    )]
+    corpus: Vec<PathBuf>,
+
+    #[arg(
+        short = 's',
+        long,
+        help = "Spread inputs from initial corpus between cores",
+        name = "SPREAD_CORPUS",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = true,
+        default_missing_value = "true",
+        overrides_with = "NO_SPREAD_CORPUS"
+    )]
+    spread_corpus: bool,
+
+    #[arg(
+        long,
+        help = "Give every core the whole initial corpus",
+        name = "NO_SPREAD_CORPUS",
+        overrides_with = "SPREAD_CORPUS"
+    )]
+    no_spread_corpus: bool,
+
+    #[arg(
+        short = 'p',
+        long,
+        help = "Choose the broker TCP port",
+        name = "PORT",
+        default_value = "7777"
+    )]
+    broker_port: u16,
+
+    #[arg(
+        long,
+        value_parser = Cores::from_cmdline,
+        help = "Spawn a client in each of the provided cores. Broker runs in the 0th core. 'all' to select all available cores. eg: '1,2-4,6' selects the cores 1,2,3,4,6.",
+        name = "CORES",
+        required = true
+    )]
+    cores: Option<Cores>,
+
+    #[arg(
+        short = 'o',
+        long,
+        help = "Output directory, each run creates new campaign directory inside with crashes, queue, coverage, stats and config",
+        name = "OUT",
+        default_value = "./campaigns",
+        global = true
+    )]
+    out: PathBuf,
+
+    #[arg(
+        long,
+        help = "Campaign directory to resume or export (latest campaign in output directory by default)",
+        name = "CAMPAIGN",
+        global = true
+    )]
+    campaign: Option<PathBuf>,
+
+    #[arg(
+        short = 'r',
+        long,
+        help = "Resume previous campaign: keep coverage data and continue from saved queue and feedback state of each core.",
+        name = "RESUME"
+    )]
+    resume: bool,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Every N seconds write queue of each client to <campaign>/<core>/corpus.txt in seed corpus format",
+        name = "DUMP_CORPUS_INTERVAL"
+    )]
+    dump_corpus_interval: Option<u64>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Only dump inputs found by given feedback",
+        name = "DUMP_FOUND_BY",
+        requires = "DUMP_CORPUS_INTERVAL"
+    )]
+    dump_found_by: Option<FeedbackKind>,
+
+    #[arg(
+        long,
+        help = "Add N generated scripts with well-formed opcode arguments to initial corpus of each core",
+        name = "GENERATE_SEEDS",
+        default_value_t = 0
+    )]
+    generate_seeds: usize,
+
+    #[arg(
+        long,
+        help = "Let mutations load up to N other scripts below entry script, so that it returns into them (harnesses must support programs)",
+        name = "MAX_CALLEES",
+        default_value_t = 0
+    )]
+    max_callees: usize,
+
+    #[arg(
+        long,
+        help = "Let mutations push up to N typed items to evaluation stack before entry script runs (harnesses must support programs)",
+        name = "MAX_ARGS",
+        default_value_t = 0
+    )]
+    max_args: usize,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_syscall_group,
+        help = "Run scripts in an engine with in-memory storage and generate calls of interop services of given groups: Runtime, Storage, Crypto, Contract (harnesses must support engine mode)",
+        name = "SYSCALLS"
+    )]
+    syscalls: Vec<String>,
+
+    #[arg(
+        long,
+        help = "JSON file with execution limits passed to both harnesses, e.g. {\"max_stack_size\": 2048, \"gas_limit\": 2000000000}, missing ones are Neo defaults",
+        name = "LIMITS"
+    )]
+    limits: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Let mutations override limits of single inputs with values around the profile",
+        name = "FUZZ_LIMITS"
+    )]
+    fuzz_limits: bool,
+
+    #[arg(
+        long,
+        help = "Report halted scripts whose stacks differ in which items are the same object (harnesses must print item ids)",
+        name = "COMPARE_ALIASING"
+    )]
+    compare_aliasing: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_opcode_name,
+        help = "Bias generation, mutation and scheduling towards given opcodes, eg: 'MODMUL,MODPOW,PACKMAP'",
+        name = "TARGET_OPCODES"
+    )]
+    target_opcodes: Vec<String>,
+
+    #[arg(
+        long,
+        help = "JSON file with Go coverage filter: {\"packages\": [...], \"exclude_packages\": [...], \"files\": [...], \"exclude_files\": [...]}",
+        name = "GO_COVER_FILTER"
+    )]
+    go_cover_filter: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Collect Go coverage of packages matching pattern ('...' matches any string), can be repeated [default: neo-go VM packages]",
+        name = "GO_COVER_PKG"
+    )]
+    go_cover_pkg: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Ignore Go coverage of packages matching pattern, can be repeated",
+        name = "GO_COVER_EXCLUDE_PKG"
+    )]
+    go_cover_exclude_pkg: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Collect Go coverage only of files matching pattern ('*' matches any string), can be repeated",
+        name = "GO_COVER_FILE"
+    )]
+    go_cover_file: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Ignore Go coverage of files matching pattern, can be repeated",
+        name = "GO_COVER_EXCLUDE_FILE"
+    )]
+    go_cover_exclude_file: Vec<String>,
+
+    #[arg(skip)]
+    resolved_go_cover_filter: GoCoverFilter,
+
+    #[arg(skip)]
+    resolved_limits: Option<Limits>,
+
+    #[arg(skip)]
+    recorded: Option<Config>,
+
+    #[arg(
+        long,
+        help = "Seed for random number generators of all clients and initial corpus split. Random if not set, either way it is saved to <campaign>/config.json",
+        name = "SEED"
+    )]
+    seed: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Print resolved configuration and exit without fuzzing",
+        name = "DRY_RUN"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export queue of previous campaign in seed corpus format (standard base64 line per script)
+    Export {
+        #[arg(long, help = "Write corpus to file instead of stdout", name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Only export inputs found by given feedback",
+            name = "FOUND_BY"
+        )]
+        found_by: Option<FeedbackKind>,
+    },
+    /// Merge coverage of all clients of campaign and write text profile, per-file percentages and HTML report
+    Coverage,
+}
+
+/// Fuzzing options after resolving `--no-...` flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub detect_status_diff: bool,
+    pub detect_crash_diff: bool,
+    pub spec_oracle: bool,
+    pub read_corpus_from_file: bool,
+    pub corpus: Vec<PathBuf>,
+    pub spread_corpus: bool,
+    pub broker_port: u16,
+    pub cores: Cores,
+    pub out: PathBuf,
+    pub resume: bool,
+    pub dump_corpus_interval: Option<u64>,
+    pub dump_found_by: Option<FeedbackKind>,
+    pub generate_seeds: usize,
+    pub target_opcodes: Vec<String>,
+    pub max_callees: usize,
+    pub max_args: usize,
+    pub syscalls: Vec<String>,
+    /// Profile from `--limits`, harnesses run with their defaults if it is not set (and limits are not fuzzed).
+    pub limits: Option<Limits>,
+    pub fuzz_limits: bool,
+    pub compare_aliasing: bool,
+    pub go_cover_filter: GoCoverFilter,
+    pub seed: u64,
+}
+
+impl Args {
+    /// Parse command line arguments, exit with error if options contradict each other.
+    pub fn parse_validated() -> Args {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        if args.resume && args.command.is_none() {
+            args.recorded = args.recorded_config();
+        }
+        // Pick random seed once, so it is the same for every client and can be recorded.
+        args.seed.get_or_insert_with(current_nanos);
+        args.resolved_go_cover_filter = args.resolve_go_cover_filter();
+        args.resolved_limits = args.resolve_limits();
+        if args.command.is_none() {
+            args.validate(&matches);
+        }
+        args
+    }
+
+    fn validate(&self, matches: &ArgMatches) {
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let config = self.config();
+        if let Some(recorded) = &self.recorded {
+            let given_config = serde_json::to_value(self.given_config()).unwrap();
+            let recorded = serde_json::to_value(recorded).unwrap();
+            for (option, ids) in RECORDED_OPTIONS {
+                if let Some(id) = ids.iter().find(|id| given(id)) {
+                    if given_config[option] != recorded[option] {
+                        conflict(&format!(
+                            "{} differs from {option} {} of the resumed campaign",
+                            arg_name(id),
+                            recorded[option]
+                        ));
+                    }
+                }
+            }
+        }
+        if given("CAMPAIGN") && !config.resume {
+            conflict("--campaign is set, but --resume is not");
+        }
+        if !config.read_corpus_from_file {
+            if given("CORPUS") {
+                conflict("--corpus is set, but reading initial corpus is disabled");
+            }
+            if given("SPREAD_CORPUS") && config.spread_corpus {
+                conflict("--spread-corpus is set, but reading initial corpus is disabled");
+            }
+        }
+    }
+
+    fn resolve_limits(&self) -> Option<Limits> {
+        let path = self.limits.as_ref()?;
+        let limits = fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| {
+                Args::command()
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!("invalid limits {}: {err}", path.display()),
+                    )
+                    .exit()
+            });
+        Some(limits)
+    }
+
+    /// Combine filter from file with the one from command line, use default packages if none are set.
+    fn resolve_go_cover_filter(&self) -> GoCoverFilter {
+        let mut filter = match &self.go_cover_filter {
+            Some(path) => fs::read(path)
+                .map_err(|err| err.to_string())
+                .and_then(|data| serde_json::from_slice(&data).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    Args::command()
+                        .error(
+                            ErrorKind::InvalidValue,
+                            format!("invalid Go coverage filter {}: {err}", path.display()),
+                        )
+                        .exit()
+                }),
+            None => GoCoverFilter::default(),
+        };
+        filter.packages.extend(self.go_cover_pkg.iter().cloned());
+        filter
+            .exclude_packages
+            .extend(self.go_cover_exclude_pkg.iter().cloned());
+        filter.files.extend(self.go_cover_file.iter().cloned());
+        filter
+            .exclude_files
+            .extend(self.go_cover_exclude_file.iter().cloned());
+        if filter.packages.is_empty() {
+            filter.packages = DEFAULT_GO_COVER_PACKAGES
+                .iter()
+                .map(|p| String::from(*p))
+                .collect();
+        }
+        filter
+    }
+
+    /// Campaign directory to continue (or export), given explicitly or the latest one.
+    pub fn previous_campaign(&self) -> Option<PathBuf> {
+        self.campaign
+            .clone()
+            .or_else(|| campaign::latest(self.out.as_path()))
+    }
+
+    /// Config saved to `config.json` of the campaign to resume, `None` if there is no campaign.
+    fn recorded_config(&self) -> Option<Config> {
+        let path = self.previous_campaign()?.join("config.json");
+        let config = fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| {
+                Args::command()
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "invalid config of resumed campaign {}: {err}",
+                            path.display()
+                        ),
+                    )
+                    .exit()
+            });
+        Some(config)
+    }
+
+    /// Config of the campaign: the recorded one when resuming, with cores, port and corpus dumping
+    /// taken from the command line.
+    pub fn config(&self) -> Config {
+        let given = self.given_config();
+        match &self.recorded {
+            Some(recorded) => Config {
+                cores: given.cores,
+                broker_port: given.broker_port,
+                out: given.out,
+                resume: given.resume,
+                dump_corpus_interval: given.dump_corpus_interval,
+                dump_found_by: given.dump_found_by,
+                ..recorded.clone()
+            },
+            None => given,
+        }
+    }
+
+    fn given_config(&self) -> Config {
+        Config {
+            detect_status_diff: self.detect_status_diff && !self.no_detect_status_diff,
+            detect_crash_diff: self.detect_crash_diff && !self.no_detect_crash_diff,
+            spec_oracle: self.spec_oracle && !self.no_spec_oracle,
+            read_corpus_from_file: self.read_corpus_from_file && !self.no_read_corpus_from_file,
+            corpus: self.corpus.clone(),
+            spread_corpus: self.spread_corpus && !self.no_spread_corpus,
+            broker_port: self.broker_port,
+            cores: self.cores.clone().expect("cores are required"),
+            out: self.out.clone(),
+            resume: self.resume,
+            dump_corpus_interval: self.dump_corpus_interval,
+            dump_found_by: self.dump_found_by,
+            generate_seeds: self.generate_seeds,
+            target_opcodes: self.target_opcodes.clone(),
+            max_callees: self.max_callees,
+            max_args: self.max_args,
+            syscalls: self.syscalls.clone(),
+            limits: self.resolved_limits,
+            fuzz_limits: self.fuzz_limits,
+            compare_aliasing: self.compare_aliasing,
+            go_cover_filter: self.resolved_go_cover_filter.clone(),
+            seed: self.seed.expect("seed is resolved when parsing"),
+        }
+    }
+}
+
+impl Config {
+    /// Seed for random number generator of the client running on given core.
+    pub fn core_seed(&self, core_id: CoreId) -> u64 {
+        hash_std(&[self.seed.to_le_bytes(), core_id.0.to_le_bytes()].concat())
+    }
+
+    /// Codes of target opcodes (names are validated when parsing).
+    pub fn target_codes(&self) -> Vec<u8> {
+        self.target_opcodes
+            .iter()
+            .filter_map(|name| opcode::by_name(name))
+            .map(|info| info.code)
+            .collect()
+    }
+
+    /// Limits profile passed to harnesses, `None` if they run with their own defaults.
+    pub fn limits_profile(&self) -> Option<Limits> {
+        match self.limits {
+            None if self.fuzz_limits => Some(Limits::default()),
+            limits => limits,
+        }
+    }
+
+    pub fn syscalls(&self) -> Vec<&'static Syscall> {
+        interop::in_groups(&self.syscalls)
+    }
+
+    /// Environment of harnesses: `SYSCALLS` lists names of enabled interop services in engine mode.
+    pub fn harness_env(&self) -> Vec<(&'static str, String)> {
+        let syscalls: Vec<&str> = self.syscalls().iter().map(|syscall| syscall.name).collect();
+        if syscalls.is_empty() {
+            return Vec::new();
+        }
+        vec![("SYSCALLS", syscalls.join(","))]
+    }
+}
+
+fn parse_syscall_group(group: &str) -> Result<String, String> {
+    interop::GROUPS
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(group))
+        .map(|name| String::from(*name))
+        .ok_or_else(|| format!("unknown interop group '{group}'"))
+}
+
+fn parse_opcode_name(name: &str) -> Result<String, String> {
+    opcode::by_name(name)
+        .map(|info| String::from(info.name))
+        .ok_or_else(|| format!("unknown opcode '{name}'"))
+}
+
+/// `--long` name of the argument with given id.
+fn arg_name(id: &str) -> String {
+    let command = Args::command();
+    let long = command
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .and_then(|arg| arg.get_long())
+        .expect("recorded options have long names");
+    format!("--{long}")
+}
+
+fn conflict(msg: &str) -> ! {
+    Args::command()
+        .error(ErrorKind::ArgumentConflict, msg)
+        .exit()
+}
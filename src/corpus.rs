@@ -0,0 +1,242 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use libafl::{corpus::Testcase, inputs::Input, Error, HasMetadata};
+use libafl_bolts::serdeany::SerdeAnyMap;
+use serde::Deserialize;
+
+use crate::{
+    asm::assemble,
+    feedback::{FeedbackKind, FoundByMetadata},
+    input::{ByteCodeInput, ProgramInput},
+    nef::{Manifest, Nef},
+};
+
+/// Part of `.<testcase>.metadata` file written by `InMemoryOnDiskCorpus` that we care about.
+#[derive(Deserialize)]
+struct QueueEntryMetadata {
+    metadata: SerdeAnyMap,
+}
+
+/// Read testcases (with their metadata) saved to the queue directory.
+pub fn read_queue(queue_dir: &Path) -> Result<Vec<Testcase<ProgramInput>>, Error> {
+    let mut testcases = Vec::new();
+    if !queue_dir.exists() {
+        return Ok(testcases);
+    }
+    for entry in fs::read_dir(queue_dir)? {
+        let path = entry?.path();
+        let file_name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        if !path.is_file() || file_name.starts_with('.') {
+            continue;
+        }
+        let mut testcase = Testcase::new(ProgramInput::from_file(path.as_path())?);
+        let metadata_path = queue_dir.join(format!(".{file_name}.metadata"));
+        if metadata_path.exists() {
+            let entry: QueueEntryMetadata = serde_json::from_slice(&fs::read(metadata_path)?)?;
+            *testcase.metadata_map_mut() = entry.metadata;
+        }
+        testcases.push(testcase);
+    }
+    Ok(testcases)
+}
+
+/// Where queue of a previous run is kept until its testcases are added to the new corpus.
+fn taken_queue_dir(queue_dir: &Path) -> PathBuf {
+    queue_dir.with_extension("resumed")
+}
+
+/// Move queue directory of a previous run aside and read its testcases, so they can be added to the new corpus
+/// without clashing with old files. Call [`finish_take_queue`] once they are added. If a previous resume was
+/// interrupted before that, the queue moved aside is read again and the partially filled one is dropped.
+pub fn take_queue(queue_dir: &Path) -> Result<Vec<Testcase<ProgramInput>>, Error> {
+    let taken_dir = taken_queue_dir(queue_dir);
+    if taken_dir.exists() {
+        if queue_dir.exists() {
+            fs::remove_dir_all(queue_dir)?;
+        }
+    } else if queue_dir.exists() {
+        fs::rename(queue_dir, taken_dir.as_path())?;
+    }
+    read_queue(taken_dir.as_path())
+}
+
+/// Remove queue of a previous run after its testcases were added to the new corpus.
+pub fn finish_take_queue(queue_dir: &Path) -> Result<(), Error> {
+    let taken_dir = taken_queue_dir(queue_dir);
+    if taken_dir.exists() {
+        fs::remove_dir_all(taken_dir)?;
+    }
+    Ok(())
+}
+
+/// Check if testcase was added to the queue by given feedback (any feedback if `None`).
+pub fn is_found_by<I: Input>(testcase: &Testcase<I>, found_by: Option<FeedbackKind>) -> bool {
+    match found_by {
+        None => true,
+        Some(kind) => testcase
+            .metadata::<FoundByMetadata>()
+            .is_ok_and(|metadata| metadata.contains(kind)),
+    }
+}
+
+/// Write inputs in the seed corpus format (one program per line), skipping duplicates.
+pub fn write_seeds<'a, W, I>(out: &mut W, inputs: I) -> io::Result<usize>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a ProgramInput>,
+{
+    let mut seen = HashSet::new();
+    for input in inputs {
+        let line = input.as_standard_base64();
+        if seen.insert(line.clone()) {
+            writeln!(out, "{line}")?;
+        }
+    }
+    Ok(seen.len())
+}
+
+/// Export queues of all clients found in work directory as seed corpus.
+pub fn export_queues(
+    work_dir: &Path,
+    output: Option<&Path>,
+    found_by: Option<FeedbackKind>,
+) -> Result<usize, Error> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(work_dir)? {
+        let queue_dir = entry?.path().join("queue");
+        for testcase in read_queue(queue_dir.as_path())? {
+            if is_found_by(&testcase, found_by) {
+                inputs.extend(testcase.input().clone());
+            }
+        }
+    }
+    let count = match output {
+        Some(path) => write_seeds(&mut BufWriter::new(File::create(path)?), &inputs)?,
+        None => write_seeds(&mut io::stdout().lock(), &inputs)?,
+    };
+    Ok(count)
+}
+
+const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// Load seed scripts from files and directories (recursively). Format of each file depends on extension:
+/// raw script (`.bin`), NEF file (`.nef`), assembler text (`.asm`), otherwise standard base64 script per line
+/// (callee scripts may follow the entry one, separated by commas).
+/// Manifests (`.manifest.json`) are read together with NEF files of the same name, they add a seed per method.
+pub fn load_seeds(paths: &[PathBuf]) -> Result<Vec<ProgramInput>, Error> {
+    let mut seeds = Vec::new();
+    for path in paths {
+        load_seeds_from(path, &mut seeds)?;
+    }
+    Ok(seeds)
+}
+
+fn load_seeds_from(path: &Path, seeds: &mut Vec<ProgramInput>) -> Result<(), Error> {
+    if path.is_dir() {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with('.') && !name.ends_with(MANIFEST_SUFFIX) {
+                entries.push(entry.path());
+            }
+        }
+        entries.sort();
+        for entry in entries {
+            load_seeds_from(&entry, seeds)?;
+        }
+        return Ok(());
+    }
+
+    let data = fs::read(path).map_err(|err| {
+        Error::illegal_argument(format!("failed to read {}: {err}", path.display()))
+    })?;
+    let invalid = |msg: String| Error::illegal_argument(format!("{}: {msg}", path.display()));
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("bin") => seeds.push(ByteCodeInput { opcodes: data }.into()),
+        Some("nef") => load_contract(path, Nef::parse(&data).map_err(invalid)?, seeds)?,
+        Some("asm") => seeds.push(
+            ByteCodeInput {
+                opcodes: assemble(&String::from_utf8_lossy(&data)).map_err(invalid)?,
+            }
+            .into(),
+        ),
+        _ => {
+            for (i, line) in String::from_utf8_lossy(&data).lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let program = ProgramInput::from_standard_base64(line)
+                    .map_err(|err| invalid(format!("line {}: {err}", i + 1)))?;
+                seeds.push(program);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whole script of the contract and, if it has a manifest, a program per method that jumps to the method
+/// with default arguments of its parameter types.
+fn load_contract(path: &Path, nef: Nef, seeds: &mut Vec<ProgramInput>) -> Result<(), Error> {
+    let manifest_path = path.with_extension("manifest.json");
+    let manifest = match fs::read(&manifest_path) {
+        Ok(data) => Some(Manifest::parse(&data).map_err(|err| {
+            Error::illegal_argument(format!("{}: {err}", manifest_path.display()))
+        })?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            return Err(Error::illegal_argument(format!(
+                "failed to read {}: {err}",
+                manifest_path.display()
+            )))
+        }
+    };
+    let mut summary = format!("{}: compiled by {}", path.display(), nef.compiler);
+    if !nef.tokens.is_empty() {
+        let tokens: Vec<String> = nef.tokens.iter().map(ToString::to_string).collect();
+        summary += &format!(", calls {}", tokens.join(", "));
+    }
+    if let Some(manifest) = &manifest {
+        let mut methods = Vec::new();
+        for method in &manifest.abi.methods {
+            let Some(entry) = method.entry(&nef.script) else {
+                eprintln!(
+                    "{}: offset {} of method {} is outside of the script",
+                    manifest_path.display(),
+                    method.offset,
+                    method.name
+                );
+                continue;
+            };
+            seeds.push(ProgramInput {
+                entry: ByteCodeInput { opcodes: entry },
+                callees: Vec::new(),
+                args: method.args(),
+                limits: None,
+            });
+            methods.push(format!("{}@{}", method.name, method.offset));
+        }
+        summary += &format!(
+            ", contract {} methods {}",
+            manifest.name,
+            methods.join(", ")
+        );
+    }
+    println!("{summary}");
+    seeds.push(
+        ByteCodeInput {
+            opcodes: nef.script,
+        }
+        .into(),
+    );
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use libafl::Error;
+
+/// Sources of neo-go, needed to render HTML report.
+const NEO_GO_DIR: &str = "./neo-go";
+const VM_PACKAGE: &str = "github.com/nspcc-dev/neo-go/pkg/vm";
+
+/// Merge coverage of all clients in campaign directory into `<campaign>/coverage` and
+/// write text profile, per-file percentages and HTML report of the VM package there.
+pub fn report(campaign_dir: &Path) -> Result<PathBuf, Error> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(campaign_dir)? {
+        let merged_dir = entry?.path().join("go-cover-merged");
+        if merged_dir.is_dir() && fs::read_dir(merged_dir.as_path())?.next().is_some() {
+            inputs.push(merged_dir);
+        }
+    }
+    if inputs.is_empty() {
+        return Err(Error::illegal_state(format!(
+            "no coverage data found in {}",
+            campaign_dir.display()
+        )));
+    }
+    inputs.sort();
+
+    let coverage_dir = campaign_dir.join("coverage");
+    let merged_dir = coverage_dir.join("merged");
+    if merged_dir.exists() {
+        fs::remove_dir_all(merged_dir.as_path())?;
+    }
+    fs::create_dir_all(merged_dir.as_path())?;
+    let coverage_dir = fs::canonicalize(coverage_dir)?;
+    let merged_dir = fs::canonicalize(merged_dir)?;
+
+    let inputs: Vec<String> = inputs.iter().map(|dir| dir.display().to_string()).collect();
+    go(Command::new("go")
+        .arg("tool")
+        .arg("covdata")
+        .arg("merge")
+        .arg(format!("-i={}", inputs.join(",")))
+        .arg("-o")
+        .arg(merged_dir.as_path()))?;
+
+    let profile_path = coverage_dir.join("profile.txt");
+    go(Command::new("go")
+        .arg("tool")
+        .arg("covdata")
+        .arg("textfmt")
+        .arg("-i")
+        .arg(merged_dir.as_path())
+        .arg("-o")
+        .arg(profile_path.as_path()))?;
+    fs::write(
+        coverage_dir.join("files.txt"),
+        file_percentages(&fs::read_to_string(profile_path.as_path())?),
+    )?;
+
+    let vm_profile_path = coverage_dir.join("profile-vm.txt");
+    go(Command::new("go")
+        .arg("tool")
+        .arg("covdata")
+        .arg("textfmt")
+        .arg("-i")
+        .arg(merged_dir.as_path())
+        .arg("-o")
+        .arg(vm_profile_path.as_path())
+        .arg("-pkg")
+        .arg(VM_PACKAGE))?;
+    go(Command::new("go")
+        .current_dir(NEO_GO_DIR)
+        .arg("tool")
+        .arg("cover")
+        .arg(format!("-html={}", vm_profile_path.display()))
+        .arg("-o")
+        .arg(coverage_dir.join("vm.html")))?;
+
+    Ok(coverage_dir)
+}
+
+fn go(cmd: &mut Command) -> Result<(), Error> {
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(Error::illegal_state(format!(
+            "{cmd:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Count covered statements for each file in text profile (`file:start,end statements count`).
+fn file_percentages(profile: &str) -> String {
+    let mut files: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    for line in profile.lines() {
+        if line.starts_with("mode") {
+            continue;
+        }
+        let data: Vec<_> = line.split(' ').collect();
+        let (Some(location), Some(statements), Some(count)) =
+            (data.first(), data.get(1), data.get(2))
+        else {
+            continue;
+        };
+        let file = location.split(':').next().unwrap_or(location);
+        let statements: u64 = statements.parse().unwrap_or(0);
+        let entry = files.entry(file).or_default();
+        entry.1 += statements;
+        if *count != "0" {
+            entry.0 += statements;
+        }
+    }
+    let mut report = String::new();
+    let (mut total_covered, mut total) = (0, 0);
+    for (file, (covered, statements)) in files {
+        total_covered += covered;
+        total += statements;
+        writeln!(report, "{file}\t{:.1}%", percent(covered, statements)).unwrap();
+    }
+    writeln!(report, "total\t{:.1}%", percent(total_covered, total)).unwrap();
+    report
+}
+
+fn percent(covered: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
@@ -0,0 +1,236 @@
+use std::borrow::Cow;
+
+use libafl::{
+    mutators::{MutationResult, Mutator, Tokens},
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    input::ByteCodeInput,
+    opcode::{self, *},
+    script,
+};
+
+/// Integers wider than this can't be pushed, CONVERT to integer fails for them.
+pub const MAX_INTEGER_SIZE: usize = 32;
+
+/// Limits of NeoVM (and values right next to them) that are used as sizes, counts and shifts.
+const LIMITS: &[i64] = &[
+    32,      // MaxIntegerSize
+    256,     // MaxShift
+    1024,    // MaxInvocationStackSize
+    2048,    // MaxStackSize
+    65535,   // MaxComparableSize - 1
+    131070,  // MaxItemSize
+    1 << 20, // old MaxItemSize
+    i32::MAX as i64,
+];
+
+/// Bit positions for which 2^n and -2^n (±1) are used.
+const POWERS: &[usize] = &[7, 8, 15, 16, 31, 32, 63, 64, 127, 128, 254, 255];
+
+/// Interesting integers as minimal little-endian two's complement, all of them can be pushed.
+pub fn integers() -> Vec<Vec<u8>> {
+    let mut values: Vec<Vec<u8>> = (-1..=16).map(from_i64).collect();
+    for limit in LIMITS {
+        for delta in -1..=1 {
+            values.push(from_i64(limit + delta));
+        }
+    }
+    for &n in POWERS {
+        for negative in [false, true] {
+            for delta in -1..=1 {
+                values.push(power_of_two(n, negative, delta));
+            }
+        }
+    }
+    values.retain(|value| value.len() <= MAX_INTEGER_SIZE);
+    values.sort();
+    values.dedup();
+    values
+}
+
+/// Interesting byte strings: integer boundaries as data, too wide integers, hashes, keys and broken UTF-8.
+pub fn byte_strings() -> Vec<Vec<u8>> {
+    let mut values = vec![
+        vec![],
+        vec![0x00],
+        vec![0xFF],
+        vec![0x80],
+        vec![0x00; MAX_INTEGER_SIZE],
+        vec![0xFF; MAX_INTEGER_SIZE],
+        vec![0x00; MAX_INTEGER_SIZE + 1],
+        vec![0xFF; MAX_INTEGER_SIZE + 1],
+        // 2^255 and -2^255 - 1 don't fit into 32 bytes.
+        power_of_two(255, false, 0),
+        power_of_two(255, true, -1),
+        vec![0x00; 20],
+        [&[0x02][..], &[0x00; 32]].concat(),
+        [&[0x03][..], &[0xFF; 32]].concat(),
+        b"NEO".to_vec(),
+        vec![0xC3, 0x28],
+        vec![0xF0, 0x9F, 0x92, 0xA9],
+        vec![0xED, 0xA0, 0x80],
+    ];
+    values.sort();
+    values.dedup();
+    values
+}
+
+/// Push instructions of all interesting values, to be used by token mutators.
+pub fn tokens() -> Tokens {
+    let mut tokens = Tokens::new();
+    for value in integers() {
+        tokens.add_token(&push_integer(&value, 0));
+    }
+    for value in byte_strings() {
+        tokens.add_token(&push_data(&value));
+    }
+    tokens
+}
+
+fn from_i64(value: i64) -> Vec<u8> {
+    minimize(value.to_le_bytes().to_vec())
+}
+
+/// ±2^n + delta.
+fn power_of_two(n: usize, negative: bool, delta: i64) -> Vec<u8> {
+    let mut value = vec![0u8; MAX_INTEGER_SIZE + 2];
+    value[n / 8] = 1 << (n % 8);
+    if negative {
+        for byte in value.iter_mut() {
+            *byte = !*byte;
+        }
+        add(&mut value, 1);
+    }
+    add(&mut value, delta);
+    minimize(value)
+}
+
+/// Add small number to two's complement integer, wrapping at its width.
+pub fn add(value: &mut [u8], delta: i64) {
+    let extension = if delta < 0 { 0xFF } else { 0x00 };
+    let mut carry = 0u16;
+    for (i, byte) in value.iter_mut().enumerate() {
+        let other = delta.to_le_bytes().get(i).copied().unwrap_or(extension);
+        let sum = *byte as u16 + other as u16 + carry;
+        *byte = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+/// Strip high bytes that only repeat the sign.
+pub fn minimize(mut value: Vec<u8>) -> Vec<u8> {
+    while let [.., prev, last] = value[..] {
+        let redundant = (last == 0x00 && prev < 0x80) || (last == 0xFF && prev >= 0x80);
+        if !redundant {
+            break;
+        }
+        value.pop();
+    }
+    if value.is_empty() {
+        value.push(0);
+    }
+    value
+}
+
+/// Smallest PUSHINT that fits the integer and is at least `min_size` bytes wide.
+fn push_integer(value: &[u8], min_size: usize) -> Vec<u8> {
+    let (code, size) = (PUSHINT8..=PUSHINT256)
+        .map(|code| (code, 1usize << (code - PUSHINT8)))
+        .find(|(_, size)| *size >= value.len() && *size >= min_size)
+        .unwrap_or((PUSHINT256, MAX_INTEGER_SIZE));
+    let extension = if value.last().is_some_and(|byte| *byte >= 0x80) {
+        0xFF
+    } else {
+        0x00
+    };
+    let mut script = vec![code];
+    script.extend(value);
+    script.resize(1 + size, extension);
+    script
+}
+
+/// Smallest PUSHDATA that fits the data.
+fn push_data(data: &[u8]) -> Vec<u8> {
+    let len = data.len() as u32;
+    let mut script = match len {
+        0..=0xFF => vec![PUSHDATA1, len as u8],
+        0x100..=0xFFFF => [&[PUSHDATA2][..], &(len as u16).to_le_bytes()].concat(),
+        _ => [&[PUSHDATA4][..], &len.to_le_bytes()].concat(),
+    };
+    script.extend(data);
+    script
+}
+
+/// Replaces pushed integer or data with interesting value.
+#[derive(Debug, Clone)]
+pub struct InterestingValueMutator {
+    integers: Vec<Vec<u8>>,
+    byte_strings: Vec<Vec<u8>>,
+}
+
+impl Default for InterestingValueMutator {
+    fn default() -> Self {
+        Self {
+            integers: integers(),
+            byte_strings: byte_strings(),
+        }
+    }
+}
+
+impl Named for InterestingValueMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("InterestingValueMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<ByteCodeInput, S> for InterestingValueMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ByteCodeInput,
+    ) -> Result<MutationResult, Error> {
+        let pushes: Vec<_> = opcode::instructions(&input.opcodes)
+            .filter(|instr| {
+                matches!(
+                    input.opcodes[instr.offset],
+                    PUSHINT8..=PUSHINT256 | PUSHDATA1..=PUSHDATA4 | PUSHM1..=PUSH16
+                )
+            })
+            .map(|instr| (instr.offset, instr.size()))
+            .collect();
+        let rand = state.rand_mut();
+        let Some(&(offset, size)) = rand.choose(&pushes) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let code = input.opcodes[offset];
+        let replacement = if matches!(code, PUSHDATA1..=PUSHDATA4) {
+            // Integers as data are converted to the same values by CONVERT and arithmetic opcodes.
+            if rand.coinflip(0.5) {
+                push_data(rand.choose(&self.byte_strings).unwrap())
+            } else {
+                push_data(rand.choose(&self.integers).unwrap())
+            }
+        } else {
+            // Keep the width of PUSHINT if value fits.
+            push_integer(rand.choose(&self.integers).unwrap(), size - 1)
+        };
+        if input.opcodes.len() - size + replacement.len() > state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        match script::splice(&input.opcodes, offset..offset + size, &replacement) {
+            Some(opcodes) => {
+                input.opcodes = opcodes;
+                Ok(MutationResult::Mutated)
+            }
+            None => Ok(MutationResult::Skipped),
+        }
+    }
+}
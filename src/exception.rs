@@ -0,0 +1,312 @@
+use std::borrow::Cow;
+
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    generator::ScriptGenerator,
+    input::ByteCodeInput,
+    opcode::*,
+    script::{self, jump_offsets},
+};
+
+/// Try regions are not nested deeper than this (`MaxTryNestingDepth` is 16).
+const MAX_DEPTH: usize = 4;
+
+/// Number of statements in a generated block.
+const MAX_STATEMENTS: usize = 3;
+
+/// Script with jumps to labels that are resolved when it is finished.
+struct Builder {
+    code: Vec<u8>,
+    /// Use `*_L` forms of jumps.
+    long: bool,
+    labels: Vec<Option<usize>>,
+    /// Offset of instruction, offset of its operand, operand size and label.
+    fixups: Vec<(usize, usize, usize, usize)>,
+}
+
+impl Builder {
+    fn new(long: bool) -> Self {
+        Self {
+            code: Vec::new(),
+            long,
+            labels: Vec::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn bind(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    /// Short form of jump opcode is given, `None` targets are left zero (no catch or finally block of `TRY`).
+    fn jump(&mut self, code: u8, targets: &[Option<usize>]) {
+        let code = if self.long { code + 1 } else { code };
+        let at = self.code.len();
+        self.code.push(code);
+        for (&(pos, size), target) in jump_offsets(code).iter().zip(targets) {
+            self.code.extend(std::iter::repeat_n(0, size));
+            if let Some(label) = target {
+                self.fixups.push((at, at + 1 + pos, size, *label));
+            }
+        }
+    }
+
+    /// Returns `None` if some short jump can't reach its label.
+    fn finish(mut self) -> Option<Vec<u8>> {
+        for (at, operand, size, label) in self.fixups {
+            let to = self.labels[label].expect("label is bound");
+            script::write_offset(
+                &mut self.code[operand..operand + size],
+                to as i64 - at as i64,
+            )?;
+        }
+        Some(self.code)
+    }
+}
+
+/// Generates well-formed nested try regions with catch and finally blocks, throwing from various depths
+/// (including local functions called with `CALL`), sometimes leaving them with `RET` or `ABORT`.
+#[derive(Debug, Clone, Default)]
+pub struct TryGenerator {
+    /// Fills blocks with opcodes and their arguments.
+    snippets: ScriptGenerator,
+}
+
+impl TryGenerator {
+    /// Try region with its catch and finally blocks.
+    pub fn snippet<R: Rand>(&self, rand: &mut R) -> Vec<u8> {
+        let long = rand.coinflip(0.5);
+        self.build(rand, long)
+            .or_else(|| self.build(rand, true))
+            .expect("long jumps always fit")
+    }
+
+    fn build<R: Rand>(&self, rand: &mut R, long: bool) -> Option<Vec<u8>> {
+        let mut builder = Builder::new(long);
+        self.try_block(rand, &mut builder, 0);
+        builder.finish()
+    }
+
+    fn block<R: Rand>(&self, rand: &mut R, builder: &mut Builder, depth: usize) {
+        for _ in 0..rand.between(1, MAX_STATEMENTS) {
+            self.statement(rand, builder, depth);
+        }
+    }
+
+    fn statement<R: Rand>(&self, rand: &mut R, builder: &mut Builder, depth: usize) {
+        match rand.below(8) {
+            0 | 1 if depth < MAX_DEPTH => self.try_block(rand, builder, depth + 1),
+            2 if depth < MAX_DEPTH => self.call(rand, builder, depth + 1),
+            3 | 4 => throw(rand, builder),
+            5 if rand.coinflip(0.2) => {
+                builder
+                    .code
+                    .push(if rand.coinflip(0.8) { RET } else { ABORT })
+            }
+            _ => builder.code.extend(self.snippets.snippet(rand)),
+        }
+    }
+
+    /// `TRY` body `ENDTRY` [catch: body `ENDTRY`] [finally: body `ENDFINALLY`], at least one of the blocks is there.
+    fn try_block<R: Rand>(&self, rand: &mut R, builder: &mut Builder, depth: usize) {
+        let has_catch = rand.coinflip(0.7);
+        let has_finally = !has_catch || rand.coinflip(0.4);
+        let (catch, finally, end) = (builder.label(), builder.label(), builder.label());
+        builder.jump(
+            TRY,
+            &[has_catch.then_some(catch), has_finally.then_some(finally)],
+        );
+        self.block(rand, builder, depth);
+        builder.jump(ENDTRY, &[Some(end)]);
+        if has_catch {
+            builder.bind(catch);
+            // Exception is on the stack: drop it, rethrow it or leave it there.
+            match rand.below(3) {
+                0 => builder.code.push(DROP),
+                1 => builder.code.push(THROW),
+                _ => (),
+            }
+            if rand.coinflip(0.5) {
+                self.block(rand, builder, depth);
+            }
+            builder.jump(ENDTRY, &[Some(end)]);
+        }
+        if has_finally {
+            builder.bind(finally);
+            if rand.coinflip(0.5) {
+                self.block(rand, builder, depth);
+            }
+            builder.code.push(ENDFINALLY);
+        }
+        builder.bind(end);
+    }
+
+    /// Local function that is jumped over and then called: `JMP` over, function: body `RET`, over: `CALL` function.
+    fn call<R: Rand>(&self, rand: &mut R, builder: &mut Builder, depth: usize) {
+        let (function, over) = (builder.label(), builder.label());
+        builder.jump(JMP, &[Some(over)]);
+        builder.bind(function);
+        self.block(rand, builder, depth);
+        builder.code.push(RET);
+        builder.bind(over);
+        builder.jump(CALL, &[Some(function)]);
+    }
+}
+
+/// Pushes integer or short byte string and throws it.
+fn throw<R: Rand>(rand: &mut R, builder: &mut Builder) {
+    if rand.coinflip(0.5) {
+        builder.code.push(PUSH0 + rand.below(17) as u8);
+    } else {
+        let len = rand.below(8);
+        builder.code.extend([PUSHDATA1, len as u8]);
+        builder.code.extend((0..len).map(|_| rand.next() as u8));
+    }
+    builder.code.push(THROW);
+}
+
+/// Inserts generated try region at instruction boundary or wraps a few instructions into a new try region.
+#[derive(Debug, Clone, Default)]
+pub struct TryMutator {
+    generator: TryGenerator,
+}
+
+impl Named for TryMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TryMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<ByteCodeInput, S> for TryMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ByteCodeInput,
+    ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let rand = state.rand_mut();
+        let boundaries = script::boundaries(&input.opcodes);
+        let opcodes = if rand.coinflip(0.5) {
+            let snippet = self.generator.snippet(rand);
+            if input.opcodes.len() + snippet.len() > max_size {
+                return Ok(MutationResult::Skipped);
+            }
+            let at = *rand.choose(&boundaries).unwrap();
+            script::splice(&input.opcodes, at..at, &snippet)
+        } else {
+            let Some(range) = script::random_range(rand, &boundaries) else {
+                return Ok(MutationResult::Skipped);
+            };
+            wrap(rand, &input.opcodes, range.start..range.end)
+        };
+        match opcodes {
+            Some(opcodes) if opcodes.len() <= max_size => {
+                input.opcodes = opcodes;
+                Ok(MutationResult::Mutated)
+            }
+            _ => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+/// Puts `TRY_L` before instructions in `range` and `ENDTRY_L` with catch and finally blocks after them,
+/// jumps to the start of the range lead to `TRY_L`, jumps to its end lead to `ENDTRY_L`.
+fn wrap<R: Rand>(rand: &mut R, script: &[u8], range: std::ops::Range<usize>) -> Option<Vec<u8>> {
+    let has_catch = rand.coinflip(0.7);
+    let has_finally = !has_catch || rand.coinflip(0.4);
+    let mut suffix = Builder::new(true);
+    let (catch, finally, end) = (suffix.label(), suffix.label(), suffix.label());
+    suffix.jump(ENDTRY, &[Some(end)]);
+    if has_catch {
+        suffix.bind(catch);
+        suffix
+            .code
+            .push(if rand.coinflip(0.5) { DROP } else { THROW });
+        suffix.jump(ENDTRY, &[Some(end)]);
+    }
+    if has_finally {
+        suffix.bind(finally);
+        suffix.code.push(ENDFINALLY);
+    }
+    suffix.bind(end);
+    // Catch and finally offsets count from TRY_L placed before the range.
+    let offset = |label: usize, has: bool| match suffix.labels[label] {
+        Some(pos) if has => (5 + 4 + range.len() + pos) as i32,
+        _ => 0,
+    };
+    let mut prefix = vec![TRY_L];
+    prefix.extend(offset(catch, has_catch).to_le_bytes());
+    prefix.extend(offset(finally, has_finally).to_le_bytes());
+    let suffix = suffix.finish()?;
+    script::surround(script, range, &prefix, &suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+
+    #[test]
+    fn wrap_keeps_jump_targets() {
+        // Loop over `NOP` with a jump out of it: JMP to RET, NOP, JMPIF back to NOP, RET.
+        let script = [JMP, 5, NOP, JMPIF, (-1i8) as u8, RET];
+        let mut rand = StdRand::with_seed(0);
+        for _ in 0..16 {
+            let wrapped = wrap(&mut rand, &script, 2..5).unwrap();
+            let instructions: Vec<_> = instructions(&wrapped).collect();
+            let target = |i: usize| {
+                let instr = &instructions[i];
+                (instr.offset as i64 + instr.operand[0] as i8 as i64) as usize
+            };
+            // JMP leads to ENDTRY_L right after the range, JMPIF leads back to TRY_L.
+            assert_eq!(wrapped[instructions[1].offset], TRY_L);
+            assert_eq!(wrapped[target(0)], ENDTRY_L);
+            assert_eq!(target(0), instructions[4].offset);
+            assert_eq!(target(3), instructions[1].offset);
+            assert_eq!(wrapped[wrapped.len() - 1], RET);
+            // Catch and finally blocks follow ENDTRY_L.
+            let try_l = &instructions[1];
+            for offset in try_l.operand.chunks(4) {
+                let offset = i32::from_le_bytes(offset.try_into().unwrap()) as usize;
+                if offset != 0 {
+                    assert!(matches!(
+                        wrapped[try_l.offset + offset],
+                        DROP | THROW | ENDFINALLY
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_keeps_missing_try_blocks() {
+        // TRY without catch block at the start of the range: TRY, NOP, its finally block, RET.
+        let script = [TRY, 0, 4, NOP, ENDFINALLY, RET];
+        let mut rand = StdRand::with_seed(0);
+        for _ in 0..16 {
+            let wrapped = wrap(&mut rand, &script, 0..3).unwrap();
+            let instructions: Vec<_> = instructions(&wrapped).collect();
+            assert_eq!(wrapped[instructions[0].offset], TRY_L);
+            let inner = &instructions[1];
+            assert_eq!(wrapped[inner.offset], TRY);
+            assert_eq!(inner.operand[0], 0);
+            assert_eq!(inner.offset + inner.operand[1] as usize, wrapped.len() - 2);
+        }
+    }
+}
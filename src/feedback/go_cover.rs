@@ -1,38 +1,58 @@
-use std::{borrow::Cow, collections::HashSet};
+use std::{borrow::Cow, collections::HashSet, path::Path};
 
 use libafl::{
+    corpus::Testcase,
     events::EventFirer,
     prelude::{Feedback, ObserversTuple},
     state::State,
+    HasMetadata,
 };
 use libafl_bolts::{
+    impl_serdeany,
     tuples::{Handle, MatchNameRef},
     Named,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::observer::GoCoverObserver;
 
+use super::{add_novelty, load_state, mark_found_by};
+
 pub struct GoCoverFeedback {
     go_cover_observer: Handle<GoCoverObserver>,
-    all_coverage: HashSet<String>,
+    state_path: Box<Path>,
+    last_result: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GoCoverFeedbackMetadata {
+    pub all_coverage: HashSet<String>,
 }
 
+impl_serdeany!(GoCoverFeedbackMetadata);
+
 impl GoCoverFeedback {
-    pub fn new(go_cover_observer: Handle<GoCoverObserver>) -> Self {
+    pub fn new(go_cover_observer: Handle<GoCoverObserver>, state_path: Box<Path>) -> Self {
         Self {
-            go_cover_observer: go_cover_observer,
-            all_coverage: HashSet::new(),
+            go_cover_observer,
+            state_path,
+            last_result: false,
         }
     }
 }
 
 impl<S> Feedback<S> for GoCoverFeedback
 where
-    S: State,
+    S: State + HasMetadata,
 {
+    fn init_state(&mut self, state: &mut S) -> Result<(), libafl::Error> {
+        state.add_metadata(load_state::<GoCoverFeedbackMetadata>(&self.state_path)?);
+        Ok(())
+    }
+
     fn is_interesting<EM, OT>(
         &mut self,
-        _state: &mut S,
+        state: &mut S,
         _manager: &mut EM,
         _input: &<S>::Input,
         observers: &OT,
@@ -46,17 +66,38 @@ where
             .get(&self.go_cover_observer)
             .expect("failed to read coverage")
             .coverage;
-        let c = self.all_coverage.clone();
-        let diff: Vec<&String> = coverage.difference(&c).collect();
+        self.last_result = false;
+        let metadata = state.metadata_mut::<GoCoverFeedbackMetadata>()?;
+        let diff: Vec<String> = coverage
+            .difference(&metadata.all_coverage)
+            .cloned()
+            .collect();
         if diff.is_empty() {
             Ok(false)
         } else {
-            for v in diff {
-                self.all_coverage.insert(v.clone());
-            }
+            metadata.all_coverage.extend(diff.iter().cloned());
+            add_novelty(state, |novelty| novelty.coverage.extend(diff));
+            self.last_result = true;
             Ok(true)
         }
     }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<<S>::Input>,
+    ) -> Result<(), libafl::Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if self.last_result {
+            mark_found_by(testcase, self.name());
+        }
+        Ok(())
+    }
 }
 
 impl Named for GoCoverFeedback {
@@ -0,0 +1,136 @@
+use std::{borrow::Cow, collections::BTreeMap, path::Path};
+
+use libafl::{
+    corpus::Testcase,
+    events::EventFirer,
+    feedbacks::{Feedback, MapFeedbackMetadata, MaxMapFeedback},
+    observers::ObserversTuple,
+    state::State,
+    Error, HasMetadata, HasNamedMetadata,
+};
+use libafl_bolts::Named;
+
+use crate::observer::{GoHitcountObserver, GO_HITCOUNT_OBSERVER};
+
+use super::{add_novelty, load_state, mark_found_by};
+
+/// [`MaxMapFeedback`] over bucketed Go hit counts whose history is saved for resuming and shared with other clients.
+pub struct HitCountFeedback {
+    inner: MaxMapFeedback<GoHitcountObserver, GoHitcountObserver, u8>,
+    state_path: Box<Path>,
+    last_result: bool,
+}
+
+impl HitCountFeedback {
+    pub fn new(observer: &GoHitcountObserver, state_path: Box<Path>) -> Self {
+        Self {
+            inner: MaxMapFeedback::new(observer),
+            state_path,
+            last_result: false,
+        }
+    }
+}
+
+/// History of [`MaxMapFeedback`], it is named after the observer.
+fn history<S: HasNamedMetadata>(state: &S) -> Option<&Vec<u8>> {
+    state
+        .named_metadata::<MapFeedbackMetadata<u8>>(GO_HITCOUNT_OBSERVER)
+        .ok()
+        .map(|metadata| &metadata.history_map)
+}
+
+/// Non-zero buckets of history by map index.
+pub fn all_hit_counts<S: HasNamedMetadata>(state: &S) -> Vec<(usize, u8)> {
+    history(state).map_or_else(Vec::new, |history| {
+        history
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| **bucket != 0)
+            .map(|(i, bucket)| (i, *bucket))
+            .collect()
+    })
+}
+
+/// Raise buckets of history to the given ones (saved by previous run or received from other client).
+pub fn merge_hit_counts<S: HasNamedMetadata>(state: &mut S, hit_counts: &[(usize, u8)]) {
+    let Ok(metadata) = state.named_metadata_mut::<MapFeedbackMetadata<u8>>(GO_HITCOUNT_OBSERVER)
+    else {
+        return;
+    };
+    for (i, bucket) in hit_counts {
+        if *i >= metadata.history_map.len() {
+            metadata.history_map.resize(*i + 1, 0);
+        }
+        let old = &mut metadata.history_map[*i];
+        if *old == 0 && *bucket != 0 {
+            metadata.num_covered_map_indexes += 1;
+        }
+        *old = (*old).max(*bucket);
+    }
+}
+
+impl<S> Feedback<S> for HitCountFeedback
+where
+    S: State + HasMetadata + HasNamedMetadata,
+    MaxMapFeedback<GoHitcountObserver, GoHitcountObserver, u8>: Feedback<S>,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        self.inner.init_state(state)?;
+        let saved: BTreeMap<usize, u8> = load_state(&self.state_path)?;
+        merge_hit_counts(state, &saved.into_iter().collect::<Vec<_>>());
+        Ok(())
+    }
+
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &S::Input,
+        observers: &OT,
+        exit_kind: &libafl::executors::ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        self.last_result = self
+            .inner
+            .is_interesting(state, manager, input, observers, exit_kind)?;
+        Ok(self.last_result)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        // History is updated with every input added to the queue, not only with the ones found by this feedback.
+        let before = history(state).cloned().unwrap_or_default();
+        self.inner
+            .append_metadata(state, manager, observers, testcase)?;
+        let raised: Vec<(usize, u8)> = all_hit_counts(state)
+            .into_iter()
+            .filter(|(i, bucket)| before.get(*i).is_none_or(|old| old != bucket))
+            .collect();
+        if !raised.is_empty() {
+            add_novelty(state, |novelty| novelty.hit_counts.extend(raised));
+        }
+        if self.last_result {
+            mark_found_by(testcase, self.name());
+        }
+        Ok(())
+    }
+}
+
+impl Named for HitCountFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("HitCountFeedback");
+        &NAME
+    }
+}
@@ -0,0 +1,120 @@
+use std::{borrow::Cow, collections::HashSet, path::Path};
+
+use libafl::{prelude::*, state::State};
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::output::parse;
+
+use super::{add_novelty, load_state, mark_found_by};
+
+/// Records last executed opcode of interesting inputs. Input is interesting itself if it is the first one
+/// to end at one of target opcodes (never without targets).
+#[derive(Clone)]
+pub struct LastOpFeedback {
+    stdout_observer: Handle<StdOutObserver>,
+    targets: Vec<u8>,
+    state_path: Box<Path>,
+    lastop: Option<u8>,
+    last_result: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LastOpMetadata {
+    pub lastop: u8,
+}
+
+impl_serdeany!(LastOpMetadata);
+
+/// Target opcodes some input has ended at.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LastOpFeedbackMetadata {
+    pub lastops: HashSet<u8>,
+}
+
+impl_serdeany!(LastOpFeedbackMetadata);
+
+impl LastOpFeedback {
+    pub fn new(
+        stdout_observer: Handle<StdOutObserver>,
+        targets: Vec<u8>,
+        state_path: Box<Path>,
+    ) -> Self {
+        Self {
+            stdout_observer,
+            targets,
+            state_path,
+            lastop: None,
+            last_result: false,
+        }
+    }
+}
+
+impl<S> Feedback<S> for LastOpFeedback
+where
+    S: State + HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_metadata(load_state::<LastOpFeedbackMetadata>(&self.state_path)?);
+        Ok(())
+    }
+
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &<S>::Input,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        self.lastop = observers
+            .get(&self.stdout_observer)
+            .and_then(|obs| obs.stdout.as_deref())
+            .and_then(parse)
+            .map(|out| out.lastop);
+        self.last_result = false;
+        let Some(lastop) = self.lastop.filter(|code| self.targets.contains(code)) else {
+            return Ok(false);
+        };
+        let metadata = state.metadata_mut::<LastOpFeedbackMetadata>()?;
+        if metadata.lastops.insert(lastop) {
+            add_novelty(state, |novelty| novelty.lastops.push(lastop));
+            self.last_result = true;
+        }
+        Ok(self.last_result)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<<S>::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if let Some(lastop) = self.lastop {
+            testcase.add_metadata(LastOpMetadata { lastop });
+        }
+        if self.last_result {
+            mark_found_by(testcase, self.name());
+        }
+        Ok(())
+    }
+}
+
+impl Named for LastOpFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("LastOpFeedback")
+    }
+}
@@ -1,2 +1,221 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use clap::ValueEnum;
+use go_cover::GoCoverFeedbackMetadata;
+use last_op::LastOpFeedbackMetadata;
+use libafl::{
+    corpus::Testcase,
+    events::{CustomBufEventResult, Event, EventFirer},
+    inputs::Input,
+    state::State,
+    Error, HasMetadata, HasNamedMetadata,
+};
+use libafl_bolts::impl_serdeany;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use type_state::{TypeState, TypeStateFeedbackMetadata};
+
 pub mod go_cover;
+pub mod hit_count;
+pub mod last_op;
 pub mod type_state;
+
+/// Tag of events carrying [`Novelty`].
+const NOVELTY_TAG: &str = "N3onDiffNovelty";
+
+/// Coverage, hit count buckets, type states and target last opcodes that were new to the client which found them.
+/// Clients broadcast it after the testcases it came with, so that all of them share one notion of "new"
+/// without skipping testcases of each other.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Novelty {
+    pub coverage: Vec<String>,
+    /// Raised buckets by index of hit counts map.
+    pub hit_counts: Vec<(usize, u8)>,
+    pub type_states: Vec<TypeState>,
+    pub lastops: Vec<u8>,
+}
+
+impl Novelty {
+    /// Collect everything client knows about, e.g. to share it after resuming campaign.
+    pub fn from_state<S>(state: &S) -> Novelty
+    where
+        S: HasMetadata + HasNamedMetadata,
+    {
+        Novelty {
+            coverage: state
+                .metadata::<GoCoverFeedbackMetadata>()
+                .map(|metadata| metadata.all_coverage.iter().cloned().collect())
+                .unwrap_or_default(),
+            hit_counts: hit_count::all_hit_counts(state),
+            type_states: state
+                .metadata::<TypeStateFeedbackMetadata>()
+                .map(|metadata| metadata.states.iter().cloned().collect())
+                .unwrap_or_default(),
+            lastops: state
+                .metadata::<LastOpFeedbackMetadata>()
+                .map(|metadata| metadata.lastops.iter().copied().collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coverage.is_empty()
+            && self.hit_counts.is_empty()
+            && self.type_states.is_empty()
+            && self.lastops.is_empty()
+    }
+
+    /// Send novelty to other clients.
+    pub fn fire<S, EM>(self, state: &mut S, manager: &mut EM) -> Result<(), Error>
+    where
+        S: State,
+        EM: EventFirer<State = S>,
+    {
+        manager.fire(
+            state,
+            Event::CustomBuf {
+                tag: String::from(NOVELTY_TAG),
+                buf: serde_json::to_vec(&self)?,
+            },
+        )
+    }
+
+    /// Merge novelty received from other client into feedback state (custom buffer handler).
+    pub fn handle<S>(state: &mut S, tag: &str, buf: &[u8]) -> Result<CustomBufEventResult, Error>
+    where
+        S: HasMetadata + HasNamedMetadata,
+    {
+        if tag != NOVELTY_TAG {
+            return Ok(CustomBufEventResult::Next);
+        }
+        let novelty: Novelty = serde_json::from_slice(buf)?;
+        if let Ok(metadata) = state.metadata_mut::<GoCoverFeedbackMetadata>() {
+            metadata.all_coverage.extend(novelty.coverage);
+        }
+        hit_count::merge_hit_counts(state, &novelty.hit_counts);
+        if let Ok(metadata) = state.metadata_mut::<TypeStateFeedbackMetadata>() {
+            metadata.states.extend(novelty.type_states);
+        }
+        if let Ok(metadata) = state.metadata_mut::<LastOpFeedbackMetadata>() {
+            metadata.lastops.extend(novelty.lastops);
+        }
+        state
+            .metadata_or_insert_with(PendingNoveltyMetadata::default)
+            .unsaved = true;
+        Ok(CustomBufEventResult::Handled)
+    }
+}
+
+/// Novelty found since it was last sent to other clients, and whether feedback state changed since it was last saved.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PendingNoveltyMetadata {
+    pub novelty: Novelty,
+    pub unsaved: bool,
+}
+
+impl_serdeany!(PendingNoveltyMetadata);
+
+/// Record novelty found by a feedback, it is sent and saved later by [`crate::stage::novelty::NoveltyStage`].
+fn add_novelty<S: HasMetadata>(state: &mut S, add: impl FnOnce(&mut Novelty)) {
+    let pending = state.metadata_or_insert_with(PendingNoveltyMetadata::default);
+    add(&mut pending.novelty);
+    pending.unsaved = true;
+}
+
+/// Feedbacks that can add inputs to the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeedbackKind {
+    TypeState,
+    GoCover,
+    HitCount,
+    LastOp,
+}
+
+impl FeedbackKind {
+    pub fn feedback_name(&self) -> &'static str {
+        match self {
+            FeedbackKind::TypeState => "TypeStateFeedback",
+            FeedbackKind::GoCover => "GoCoverFeedback",
+            FeedbackKind::HitCount => "HitCountFeedback",
+            FeedbackKind::LastOp => "LastOpFeedback",
+        }
+    }
+
+    /// File in client directory with saved state of the feedback.
+    pub fn state_file(&self) -> &'static str {
+        match self {
+            FeedbackKind::TypeState => "type-state.json",
+            FeedbackKind::GoCover => "go-cover.json",
+            FeedbackKind::HitCount => "hit-counts.json",
+            FeedbackKind::LastOp => "last-op.json",
+        }
+    }
+}
+
+/// Names of the feedbacks that found the testcase interesting.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FoundByMetadata {
+    pub feedbacks: Vec<String>,
+}
+
+impl_serdeany!(FoundByMetadata);
+
+impl FoundByMetadata {
+    pub fn contains(&self, kind: FeedbackKind) -> bool {
+        self.feedbacks
+            .iter()
+            .any(|name| name == kind.feedback_name())
+    }
+}
+
+fn mark_found_by<I: Input>(testcase: &mut Testcase<I>, feedback_name: &str) {
+    let metadata = testcase.metadata_map_mut();
+    match metadata.get_mut::<FoundByMetadata>() {
+        Some(found_by) => found_by.feedbacks.push(String::from(feedback_name)),
+        None => metadata.insert(FoundByMetadata {
+            feedbacks: vec![String::from(feedback_name)],
+        }),
+    }
+}
+
+/// Load feedback state saved by a previous run, or start from scratch if there is none.
+fn load_state<T>(path: &Path) -> Result<T, Error>
+where
+    T: DeserializeOwned + Default,
+{
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Save state of all feedbacks to client directory, so the campaign can be resumed later.
+pub fn store_states<S>(state: &S, dir: &Path) -> Result<(), Error>
+where
+    S: HasMetadata + HasNamedMetadata,
+{
+    let path = |kind: FeedbackKind| dir.join(kind.state_file());
+    if let Ok(metadata) = state.metadata::<TypeStateFeedbackMetadata>() {
+        store_state(&path(FeedbackKind::TypeState), metadata)?;
+    }
+    if let Ok(metadata) = state.metadata::<GoCoverFeedbackMetadata>() {
+        store_state(&path(FeedbackKind::GoCover), metadata)?;
+    }
+    let hit_counts: BTreeMap<usize, u8> = hit_count::all_hit_counts(state).into_iter().collect();
+    store_state(&path(FeedbackKind::HitCount), &hit_counts)?;
+    if let Ok(metadata) = state.metadata::<LastOpFeedbackMetadata>() {
+        store_state(&path(FeedbackKind::LastOp), metadata)?;
+    }
+    Ok(())
+}
+
+/// Save feedback state atomically.
+fn store_state<T>(path: &Path, state: &T) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let tmp_path = path.with_extension("tmp");
+    fs::write(tmp_path.as_path(), serde_json::to_vec(state)?)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
@@ -1,42 +1,60 @@
-use std::{borrow::Cow, collections::HashSet};
+use std::{borrow::Cow, collections::HashSet, path::Path};
 
 use libafl::{prelude::*, state::State};
 use libafl_bolts::{
+    impl_serdeany,
     tuples::{Handle, MatchNameRef},
     Named,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::output::parse;
 
+use super::{add_novelty, load_state, mark_found_by};
+
 #[derive(Clone)]
 pub struct TypeStateFeedback {
     stdout_observers: Vec<Handle<StdOutObserver>>,
-    states: HashSet<TypeState>,
+    state_path: Box<Path>,
+    last_result: bool,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
-struct TypeState {
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct TypeState {
     opcode: u8,
     fst_type: String,
     snd_type: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TypeStateFeedbackMetadata {
+    pub states: HashSet<TypeState>,
+}
+
+impl_serdeany!(TypeStateFeedbackMetadata);
+
 impl TypeStateFeedback {
-    pub fn new(stdout_observers: Vec<Handle<StdOutObserver>>) -> Self {
+    pub fn new(stdout_observers: Vec<Handle<StdOutObserver>>, state_path: Box<Path>) -> Self {
         Self {
-            stdout_observers: stdout_observers,
-            states: HashSet::new(),
+            stdout_observers,
+            state_path,
+            last_result: false,
         }
     }
 }
 
 impl<S> Feedback<S> for TypeStateFeedback
 where
-    S: State,
+    S: State + HasMetadata,
 {
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_metadata(load_state::<TypeStateFeedbackMetadata>(&self.state_path)?);
+        Ok(())
+    }
+
     fn is_interesting<EM, OT>(
         &mut self,
-        _state: &mut S,
+        state: &mut S,
         _manager: &mut EM,
         _input: &<S>::Input,
         observers: &OT,
@@ -46,7 +64,8 @@ where
         EM: EventFirer<State = S>,
         OT: ObserversTuple<S>,
     {
-        let mut new_state_found = false;
+        let metadata = state.metadata_mut::<TypeStateFeedbackMetadata>()?;
+        let mut new_states = Vec::new();
         for obs in &self.stdout_observers {
             let out = observers
                 .get(obs)
@@ -55,35 +74,54 @@ where
                 .as_ref()
                 .expect("no output found (first)")
                 .clone();
-            match parse(&out) {
-                Some(out) => {
-                    let ts = match &out.estack[..] {
-                        [fst] => TypeState {
-                            opcode: out.lastop,
-                            fst_type: fst.itype.clone(),
-                            snd_type: String::new(),
-                        },
-                        [fst, snd] => TypeState {
-                            opcode: out.lastop,
-                            fst_type: fst.itype.clone(),
-                            snd_type: snd.itype.clone(),
-                        },
-                        [fst, snd, ..] => TypeState {
-                            opcode: out.lastop,
-                            fst_type: fst.itype.clone(),
-                            snd_type: snd.itype.clone(),
-                        },
-                        _ => continue,
-                    };
-                    if self.states.insert(ts) {
-                        new_state_found = true;
-                    }
+            if let Some(out) = parse(&out) {
+                let ts = match &out.estack[..] {
+                    [fst] => TypeState {
+                        opcode: out.lastop,
+                        fst_type: fst.itype.clone(),
+                        snd_type: String::new(),
+                    },
+                    [fst, snd] => TypeState {
+                        opcode: out.lastop,
+                        fst_type: fst.itype.clone(),
+                        snd_type: snd.itype.clone(),
+                    },
+                    [fst, snd, ..] => TypeState {
+                        opcode: out.lastop,
+                        fst_type: fst.itype.clone(),
+                        snd_type: snd.itype.clone(),
+                    },
+                    _ => continue,
+                };
+                if metadata.states.insert(ts.clone()) {
+                    new_states.push(ts);
                 }
-                _ => {}
-            };
+            }
         }
+        let new_state_found = !new_states.is_empty();
+        if new_state_found {
+            add_novelty(state, |novelty| novelty.type_states.extend(new_states));
+        }
+        self.last_result = new_state_found;
         Ok(new_state_found)
     }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<<S>::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if self.last_result {
+            mark_found_by(testcase, self.name());
+        }
+        Ok(())
+    }
 }
 
 impl Named for TypeStateFeedback {
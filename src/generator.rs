@@ -0,0 +1,365 @@
+use std::borrow::Cow;
+
+use libafl::{
+    generators::Generator,
+    mutators::{MutationResult, Mutator},
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    input::ByteCodeInput,
+    interop::{self, Syscall},
+    item,
+    opcode::{self, *},
+    script,
+};
+
+/// Kind of stack item an opcode expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg {
+    Any,
+    Bool,
+    Int,
+    /// Integer small enough to be used as index, count or shift.
+    Small,
+    Bytes,
+    Buffer,
+    Array,
+    Struct,
+    Map,
+    /// Up to 4 items followed by index below their number (PACK, PICK, XDROP...).
+    Items,
+    /// Value-key pairs followed by their number (PACKMAP).
+    Pairs,
+    /// Storage context from `System.Storage.GetContext`.
+    Context,
+}
+
+/// Possible arguments of opcode, from the deepest stack item to the top one.
+/// Opcodes without signatures are never generated.
+pub fn signatures(code: u8) -> &'static [&'static [Arg]] {
+    use Arg::*;
+    match code {
+        NOP | DEPTH | CLEAR | NEWARRAY0 | NEWSTRUCT0 | NEWMAP => &[&[]],
+        ASSERT => &[&[Bool]],
+        THROW => &[&[Any]],
+        ABORTMSG => &[&[Bytes]],
+        ASSERTMSG => &[&[Bool, Bytes]],
+        DROP | DUP => &[&[Any]],
+        NIP | OVER | TUCK | SWAP => &[&[Any, Any]],
+        ROT | REVERSE3 => &[&[Any, Any, Any]],
+        REVERSE4 => &[&[Any, Any, Any, Any]],
+        XDROP | PICK | ROLL | REVERSEN | PACK | PACKSTRUCT => &[&[Items]],
+        NEWBUFFER => &[&[Small]],
+        MEMCPY => &[&[Buffer, Small, Bytes, Small, Small]],
+        CAT => &[&[Bytes, Bytes], &[Buffer, Bytes]],
+        SUBSTR => &[&[Bytes, Small, Small]],
+        LEFT | RIGHT => &[&[Bytes, Small]],
+        INVERT | SIGN | ABS | NEGATE | INC | DEC | SQRT | NZ => &[&[Int]],
+        NOT => &[&[Bool], &[Int]],
+        AND | OR | XOR => &[&[Int, Int], &[Bool, Bool]],
+        EQUAL | NOTEQUAL => &[&[Any, Any]],
+        ADD | SUB | MUL | DIV | MOD | NUMEQUAL | NUMNOTEQUAL | LT | LE | GT | GE | MIN | MAX => {
+            &[&[Int, Int]]
+        }
+        BOOLAND | BOOLOR => &[&[Bool, Bool], &[Int, Int]],
+        POW | SHL | SHR => &[&[Int, Small]],
+        MODMUL | MODPOW | WITHIN => &[&[Int, Int, Int]],
+        PACKMAP => &[&[Pairs]],
+        UNPACK => &[&[Array], &[Struct], &[Map]],
+        NEWARRAY | NEWARRAY_T | NEWSTRUCT => &[&[Small]],
+        SIZE => &[&[Bytes], &[Buffer], &[Array], &[Map], &[Int]],
+        HASKEY => &[
+            &[Array, Small],
+            &[Map, Int],
+            &[Bytes, Small],
+            &[Buffer, Small],
+        ],
+        KEYS => &[&[Map]],
+        VALUES => &[&[Array], &[Struct], &[Map]],
+        PICKITEM => &[
+            &[Array, Small],
+            &[Struct, Small],
+            &[Map, Int],
+            &[Bytes, Small],
+            &[Buffer, Small],
+        ],
+        APPEND => &[&[Array, Any], &[Struct, Any]],
+        SETITEM => &[
+            &[Array, Small, Any],
+            &[Map, Int, Any],
+            &[Buffer, Small, Small],
+        ],
+        REVERSEITEMS => &[&[Array], &[Buffer]],
+        REMOVE => &[&[Array, Small], &[Map, Int]],
+        CLEARITEMS => &[&[Array], &[Map]],
+        POPITEM => &[&[Array]],
+        ISNULL | ISTYPE | CONVERT => &[&[Any]],
+        _ => &[],
+    }
+}
+
+/// Opcodes that change their first argument in place and push nothing, it's duplicated to keep the result.
+const IN_PLACE: &[u8] = &[APPEND, SETITEM, REVERSEITEMS, REMOVE, CLEARITEMS];
+
+/// Compound items are not nested deeper than this.
+const MAX_DEPTH: usize = 2;
+
+/// Number of opcodes with arguments in a generated script.
+const MAX_SNIPPETS: usize = 8;
+
+/// Generates scripts where each opcode gets arguments of expected number and kind.
+#[derive(Debug, Clone)]
+pub struct ScriptGenerator {
+    targets: Vec<u8>,
+    /// Interop services `SYSCALL` is generated with (never if empty).
+    syscalls: Vec<&'static Syscall>,
+}
+
+impl ScriptGenerator {
+    /// Generator of given opcodes, ones without known signature are ignored.
+    pub fn new(targets: impl IntoIterator<Item = u8>) -> Option<Self> {
+        let targets: Vec<u8> = targets
+            .into_iter()
+            .filter(|code| !signatures(*code).is_empty())
+            .collect();
+        (!targets.is_empty()).then_some(Self {
+            targets,
+            syscalls: Vec::new(),
+        })
+    }
+
+    /// Also generate calls of given interop services with their arguments.
+    pub fn with_syscalls(mut self, syscalls: Vec<&'static Syscall>) -> Self {
+        if !syscalls.is_empty() {
+            self.targets.push(SYSCALL);
+        }
+        self.syscalls = syscalls;
+        self
+    }
+
+    /// Generator of target opcodes, or of all opcodes if none of them can be generated.
+    pub fn targeting(targets: &[u8]) -> Self {
+        Self::new(targets.iter().copied()).unwrap_or_default()
+    }
+
+    /// Target opcodes that can't be generated (`SYSCALL` can be if some interop services are enabled).
+    pub fn ungenerated(targets: &[u8], syscalls: bool) -> Vec<u8> {
+        targets
+            .iter()
+            .copied()
+            .filter(|code| signatures(*code).is_empty() && !(*code == SYSCALL && syscalls))
+            .collect()
+    }
+
+    /// Arguments of a random target opcode followed by the opcode itself.
+    pub fn snippet<R: Rand>(&self, rand: &mut R) -> Vec<u8> {
+        let code = *rand.choose(&self.targets).unwrap();
+        let syscall = (code == SYSCALL).then(|| *rand.choose(&self.syscalls).unwrap());
+        let args = match syscall {
+            Some(syscall) => syscall.args,
+            None => *rand.choose(signatures(code)).unwrap(),
+        };
+        let mut script = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            push_arg(rand, *arg, 0, &mut script);
+            if i == 0 && IN_PLACE.contains(&code) {
+                script.push(DUP);
+            }
+        }
+        script.push(code);
+        if let Some(syscall) = syscall {
+            script.extend(syscall.hash());
+            return script;
+        }
+        match opcode::by_code(code).map(|info| info.operand) {
+            Some(Operand::Fixed(1)) => script.push(*rand.choose(item::VALUE_TYPES).unwrap()),
+            Some(Operand::Fixed(size)) => script.extend((0..size).map(|_| rand.next() as u8)),
+            _ => (),
+        }
+        script
+    }
+}
+
+impl Default for ScriptGenerator {
+    fn default() -> Self {
+        Self::new(OPCODES.iter().map(|info| info.code)).unwrap()
+    }
+}
+
+impl<S> Generator<ByteCodeInput, S> for ScriptGenerator
+where
+    S: HasRand,
+{
+    fn generate(&mut self, state: &mut S) -> Result<ByteCodeInput, Error> {
+        let rand = state.rand_mut();
+        let mut opcodes = Vec::new();
+        for _ in 0..rand.between(1, MAX_SNIPPETS) {
+            opcodes.extend(self.snippet(rand));
+        }
+        Ok(ByteCodeInput { opcodes })
+    }
+}
+
+/// Inserts generated snippet at instruction boundary.
+#[derive(Debug, Clone, Default)]
+pub struct GenerateMutator {
+    generator: ScriptGenerator,
+}
+
+impl GenerateMutator {
+    pub fn new(generator: ScriptGenerator) -> Self {
+        Self { generator }
+    }
+}
+
+impl Named for GenerateMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("GenerateMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<ByteCodeInput, S> for GenerateMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ByteCodeInput,
+    ) -> Result<MutationResult, Error> {
+        let snippet = self.generator.snippet(state.rand_mut());
+        if input.opcodes.len() + snippet.len() > state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        let at = *state
+            .rand_mut()
+            .choose(&script::boundaries(&input.opcodes))
+            .unwrap();
+        match script::splice(&input.opcodes, at..at, &snippet) {
+            Some(opcodes) => input.opcodes = opcodes,
+            None => return Ok(MutationResult::Skipped),
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+fn push_int(value: usize, script: &mut Vec<u8>) {
+    debug_assert!(value <= 16);
+    script.push(PUSH0 + value as u8);
+}
+
+fn push_arg<R: Rand>(rand: &mut R, arg: Arg, depth: usize, script: &mut Vec<u8>) {
+    match arg {
+        Arg::Any => {
+            let kinds: &[Arg] = if depth < MAX_DEPTH {
+                &[
+                    Arg::Bool,
+                    Arg::Int,
+                    Arg::Bytes,
+                    Arg::Buffer,
+                    Arg::Array,
+                    Arg::Struct,
+                    Arg::Map,
+                    Arg::Any,
+                ]
+            } else {
+                &[Arg::Bool, Arg::Int, Arg::Bytes, Arg::Any]
+            };
+            match *rand.choose(kinds).unwrap() {
+                // Any stands for null here.
+                Arg::Any => script.push(PUSHNULL),
+                kind => push_arg(rand, kind, depth, script),
+            }
+        }
+        Arg::Bool => script.push(if rand.coinflip(0.5) { PUSHT } else { PUSHF }),
+        Arg::Int => {
+            if rand.coinflip(0.5) {
+                script.push(PUSHM1 + rand.below(18) as u8);
+            } else {
+                let size = rand.below(6);
+                script.push(PUSHINT8 + size as u8);
+                script.extend((0..1 << size).map(|_| rand.next() as u8));
+            }
+        }
+        Arg::Small => push_int(rand.below(5), script),
+        Arg::Bytes => {
+            let len = rand.below(33);
+            script.extend([PUSHDATA1, len as u8]);
+            script.extend((0..len).map(|_| rand.next() as u8));
+        }
+        Arg::Buffer => {
+            if rand.coinflip(0.5) {
+                push_arg(rand, Arg::Small, depth, script);
+                script.push(NEWBUFFER);
+            } else {
+                push_arg(rand, Arg::Bytes, depth, script);
+                script.extend([CONVERT, item::BUFFER]);
+            }
+        }
+        Arg::Array | Arg::Struct => {
+            let (empty, new, pack) = if arg == Arg::Array {
+                (NEWARRAY0, NEWARRAY, PACK)
+            } else {
+                (NEWSTRUCT0, NEWSTRUCT, PACKSTRUCT)
+            };
+            match rand.below(3) {
+                0 => script.push(empty),
+                1 => {
+                    push_arg(rand, Arg::Small, depth, script);
+                    script.push(new);
+                }
+                _ => {
+                    let count = rand.between(1, 3);
+                    for _ in 0..count {
+                        push_arg(rand, Arg::Any, depth + 1, script);
+                    }
+                    push_int(count, script);
+                    script.push(pack);
+                }
+            }
+        }
+        Arg::Map => {
+            if rand.coinflip(0.5) {
+                script.push(NEWMAP);
+            } else {
+                push_pairs(rand, depth + 1, script);
+                script.push(PACKMAP);
+            }
+        }
+        Arg::Items => {
+            let count = rand.between(1, 4);
+            for _ in 0..count {
+                push_arg(rand, Arg::Any, depth, script);
+            }
+            push_int(rand.below(count), script);
+        }
+        Arg::Pairs => push_pairs(rand, depth, script),
+        Arg::Context => {
+            script.push(SYSCALL);
+            script.extend(
+                interop::by_name("System.Storage.GetContext")
+                    .unwrap()
+                    .hash(),
+            );
+        }
+    }
+}
+
+/// PACKMAP takes key from the top and value below it.
+fn push_pairs<R: Rand>(rand: &mut R, depth: usize, script: &mut Vec<u8>) {
+    let count = rand.below(4);
+    for _ in 0..count {
+        push_arg(rand, Arg::Any, depth, script);
+        let key = if rand.coinflip(0.5) {
+            Arg::Int
+        } else {
+            Arg::Bytes
+        };
+        push_arg(rand, key, depth, script);
+    }
+    push_int(count, script);
+}
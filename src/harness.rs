@@ -0,0 +1,222 @@
+use std::process::Command;
+
+use base64::prelude::*;
+use libafl::inputs::HasTargetBytes;
+
+use crate::{
+    asm::assemble,
+    input::{ByteCodeInput, ProgramInput},
+    interop::Syscall,
+    item::Item,
+    limits::Limits,
+    opcode::{ADD, DUP, NEWARRAY0, PUSH0, PUSH1, PUSHINT64, RET, SHL},
+    output::{parse, Output, StorageItem},
+};
+
+pub const NEO_GO: &str = "./harness/neo-go";
+pub const NEO_SHARP: &str = "./harness/neo-sharp";
+
+/// Harness executable with the environment and limits profile the fuzzer runs it with.
+pub struct Harness<'a> {
+    pub path: &'a str,
+    pub env: &'a [(&'static str, String)],
+    /// `None` if harness runs with its own defaults.
+    pub limits: Option<&'a Limits>,
+}
+
+/// Second argument of harnesses: limits profile JSON, or `DUMMY` (as before limits were supported)
+/// if they run with their own defaults.
+pub fn limits_arg(limits: Option<&Limits>) -> String {
+    limits.map_or_else(
+        || String::from("DUMMY"),
+        |limits| serde_json::to_string(limits).unwrap(),
+    )
+}
+
+impl Harness<'_> {
+    /// Runs harness on probe inputs for the parts of the protocol the campaign uses,
+    /// `Err` tells which one it doesn't support.
+    pub fn check(&self, programs: bool, ids: bool, syscalls: &[&Syscall]) -> Result<(), String> {
+        if let Some(limits) = self.limits {
+            self.check_limits(limits)?;
+        }
+        if ids {
+            self.check_ids()?;
+        }
+        if programs {
+            self.check_programs()?;
+        }
+        if !syscalls.is_empty() {
+            self.check_engine(syscalls)?;
+        }
+        Ok(())
+    }
+
+    fn run(&self, input: &ProgramInput) -> Result<Output, String> {
+        let arg = String::from_utf8(input.target_bytes().to_vec()).unwrap();
+        let out = Command::new(self.path)
+            .envs(self.env.iter().cloned())
+            .arg(arg)
+            .arg(limits_arg(self.limits))
+            .output()
+            .map_err(|err| format!("failed to run: {err}"))?;
+        parse(&out.stdout).ok_or_else(|| {
+            format!(
+                "unexpected output `{}`",
+                String::from_utf8_lossy(&out.stdout).trim()
+            )
+        })
+    }
+
+    /// Limits are echoed, shift above `max_shift` faults and the one at it doesn't.
+    fn check_limits(&self, limits: &Limits) -> Result<(), String> {
+        let shift = |by: u64| {
+            let mut opcodes = vec![PUSH0, PUSHINT64];
+            opcodes.extend(by.to_le_bytes());
+            opcodes.push(SHL);
+            ProgramInput::from(ByteCodeInput { opcodes })
+        };
+        let echoed = |out: &Output| out.limits.as_ref() == Some(limits);
+        let out = self.run(&shift(limits.max_shift))?;
+        expect(&out, "limits profile", |out| {
+            out.status == "VM halted" && echoed(out)
+        })?;
+        let out = self.run(&shift(limits.max_shift.saturating_add(1)))?;
+        expect(&out, "limits profile", |out| {
+            out.status != "VM halted" && echoed(out)
+        })
+    }
+
+    /// Two references to one array and another array have ids (top item first).
+    fn check_ids(&self) -> Result<(), String> {
+        let probe = ProgramInput::from(ByteCodeInput {
+            opcodes: vec![NEWARRAY0, DUP, NEWARRAY0],
+        });
+        let out = self.run(&probe)?;
+        expect(&out, "item ids", |out| {
+            out.estack
+                .iter()
+                .map(|item| item.id)
+                .eq([Some(0), Some(1), Some(1)])
+        })
+    }
+
+    /// Argument 7 is pushed, entry script pushes 1 and returns to the callee that adds them,
+    /// limits of the program override the profile (if there is one).
+    fn check_programs(&self) -> Result<(), String> {
+        let probe = ProgramInput {
+            entry: ByteCodeInput {
+                opcodes: vec![PUSH1, RET],
+            },
+            callees: vec![ByteCodeInput {
+                opcodes: vec![ADD, RET],
+            }],
+            args: vec![Item::Integer(vec![7])],
+            limits: self.limits.map(|limits| Limits {
+                max_stack_size: limits.max_stack_size.saturating_add(1),
+                ..*limits
+            }),
+        };
+        let out = self.run(&probe)?;
+        expect(&out, "program JSON", |out| {
+            out.status == "VM halted"
+                && out.estack == [Item::Integer(vec![8]).to_stack_item()]
+                && (probe.limits.is_none() || out.limits == probe.limits)
+        })
+    }
+
+    /// Bare VM faults at `SYSCALL`, engine halts and prints effects of the script.
+    fn check_engine(&self, syscalls: &[&Syscall]) -> Result<(), String> {
+        let Some((probe, effects)) = engine_probe(syscalls) else {
+            return Ok(());
+        };
+        let out = self.run(&probe)?;
+        expect(&out, "engine mode", |out| {
+            out.status == "VM halted" && effects(out)
+        })
+    }
+}
+
+/// Check of probe output.
+type Effects = fn(&Output) -> bool;
+
+/// Script calling some of enabled services and the check of its effects, `None` if none of them can be called.
+fn engine_probe(syscalls: &[&Syscall]) -> Option<(ProgramInput, Effects)> {
+    let enabled = |name: &str| syscalls.iter().any(|syscall| syscall.name == name);
+    let (text, effects): (String, Effects) = if enabled("System.Runtime.Log") {
+        (
+            String::from("PUSHDATA1 \"probe\"\nSYSCALL System.Runtime.Log"),
+            |out| out.logs.len() == 1 && out.logs[0].message == "probe",
+        )
+    } else if enabled("System.Storage.Put") {
+        (
+            String::from(
+                "PUSHDATA1 \"v\"\nPUSHDATA1 \"k\"\n\
+                     SYSCALL System.Storage.GetContext\nSYSCALL System.Storage.Put",
+            ),
+            |out| {
+                out.storage
+                    == [StorageItem {
+                        key: BASE64_STANDARD.encode("k"),
+                        value: Some(BASE64_STANDARD.encode("v")),
+                    }]
+            },
+        )
+    } else {
+        // Crypto services fault on arguments without valid keys and signatures.
+        let syscall = syscalls.iter().find(|syscall| syscall.args.is_empty())?;
+        (format!("SYSCALL {}", syscall.name), |_| true)
+    };
+    let probe = ProgramInput::from(ByteCodeInput {
+        opcodes: assemble(&text).unwrap(),
+    });
+    Some((probe, effects))
+}
+
+fn expect(out: &Output, feature: &str, ok: impl Fn(&Output) -> bool) -> Result<(), String> {
+    if ok(out) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{feature} is not supported, probe output is {}",
+            serde_json::to_string(out).unwrap()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interop::in_groups, opcode::SYSCALL};
+
+    /// Name of the service the engine probe for given groups calls last.
+    fn last_call(groups: &[&str]) -> Option<&'static str> {
+        let syscalls = in_groups(
+            &groups
+                .iter()
+                .map(|group| group.to_string())
+                .collect::<Vec<_>>(),
+        );
+        let (probe, _) = engine_probe(&syscalls)?;
+        let opcodes = &probe.entry.opcodes;
+        assert_eq!(opcodes[opcodes.len() - 5], SYSCALL);
+        syscalls
+            .iter()
+            .find(|syscall| syscall.hash()[..] == opcodes[opcodes.len() - 4..])
+            .map(|syscall| syscall.name)
+    }
+
+    #[test]
+    fn engine_probes() {
+        assert_eq!(
+            last_call(&["Storage", "Runtime"]),
+            Some("System.Runtime.Log")
+        );
+        assert_eq!(last_call(&["Storage"]), Some("System.Storage.Put"));
+        assert_eq!(
+            last_call(&["Contract", "Crypto"]),
+            Some("System.Contract.GetCallFlags")
+        );
+        assert_eq!(last_call(&["Crypto"]), None);
+    }
+}
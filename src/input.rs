@@ -1,7 +1,15 @@
+use std::{fs, path::Path};
+
 use base64::prelude::*;
-use libafl::inputs::{HasMutatorBytes, HasTargetBytes, Input};
-use libafl_bolts::HasLen;
+use libafl::{
+    inputs::{HasMutatorBytes, HasTargetBytes, Input},
+    Error,
+};
+use libafl_bolts::{hash_std, HasLen};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{item::Item, limits::Limits, output::StackItem};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ByteCodeInput {
@@ -15,7 +23,7 @@ impl ByteCodeInput {
 }
 
 impl HasTargetBytes for ByteCodeInput {
-    fn target_bytes(&self) -> libafl_bolts::prelude::OwnedSlice<u8> {
+    fn target_bytes(&self) -> libafl_bolts::prelude::OwnedSlice<'_, u8> {
         BASE64_STANDARD
             .encode(&self.opcodes)
             .as_bytes()
@@ -63,8 +71,204 @@ impl HasLen for ByteCodeInput {
     }
 }
 
+/// Names longer than this don't fit into a file name on most file systems.
+const MAX_NAME_LEN: usize = 200;
+
 impl Input for ByteCodeInput {
     fn generate_name(&self, _: Option<libafl::prelude::CorpusId>) -> String {
-        BASE64_URL_SAFE.encode(&self.opcodes)
+        let name = BASE64_URL_SAFE.encode(&self.opcodes);
+        if name.len() <= MAX_NAME_LEN {
+            name
+        } else {
+            format!(
+                "{}-{:016x}",
+                &name[..MAX_NAME_LEN - 17],
+                hash_std(&self.opcodes)
+            )
+        }
+    }
+}
+
+/// Entry script with callee scripts loaded into the invocation stack below it: when the entry script returns,
+/// the first callee continues with its results, and so on.
+/// Arguments are pushed to the evaluation stack of entry script before running it (the last one is on top).
+/// Limits override the profile harnesses are started with.
+/// Program without callees, arguments and limits is passed to harnesses the same way as [`ByteCodeInput`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProgramInput {
+    pub entry: ByteCodeInput,
+    pub callees: Vec<ByteCodeInput>,
+    pub args: Vec<Item>,
+    pub limits: Option<Limits>,
+}
+
+impl ProgramInput {
+    pub fn is_plain_script(&self) -> bool {
+        self.callees.is_empty() && self.args.is_empty() && self.limits.is_none()
+    }
+
+    /// Entry script followed by callees.
+    pub fn scripts(&self) -> impl Iterator<Item = &ByteCodeInput> {
+        [&self.entry].into_iter().chain(&self.callees)
+    }
+
+    /// Arguments in the format of printed evaluation stack.
+    pub fn stack_items(&self) -> Vec<StackItem> {
+        self.args.iter().map(Item::to_stack_item).collect()
+    }
+
+    /// Scripts in standard base64 separated by commas, then JSON array of arguments after a space if there are any,
+    /// the format of seed corpus line.
+    pub fn as_standard_base64(&self) -> String {
+        let scripts = self
+            .scripts()
+            .map(ByteCodeInput::as_standard_base64)
+            .collect::<Vec<_>>()
+            .join(",");
+        if self.args.is_empty() {
+            return scripts;
+        }
+        format!("{scripts} {}", json!(self.stack_items()))
+    }
+
+    /// Parse seed corpus line.
+    pub fn from_standard_base64(line: &str) -> Result<Self, String> {
+        let (scripts, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let mut programs = Vec::new();
+        for script in scripts.split(',') {
+            programs.push(ByteCodeInput {
+                opcodes: BASE64_STANDARD
+                    .decode(script.trim())
+                    .map_err(|err| format!("invalid base64 ({err})"))?,
+            });
+        }
+        let args = match args.trim() {
+            "" => Vec::new(),
+            args => serde_json::from_str::<Vec<StackItem>>(args)
+                .map_err(|err| format!("invalid arguments ({err})"))?
+                .iter()
+                .map(Item::from_stack_item)
+                .collect::<Result<_, _>>()?,
+        };
+        let entry = programs.remove(0);
+        Ok(ProgramInput {
+            entry,
+            callees: programs,
+            args,
+            limits: None,
+        })
+    }
+}
+
+impl From<ByteCodeInput> for ProgramInput {
+    fn from(entry: ByteCodeInput) -> Self {
+        ProgramInput {
+            entry,
+            callees: Vec::new(),
+            args: Vec::new(),
+            limits: None,
+        }
+    }
+}
+
+/// Harnesses get other programs as JSON (starting with `{`), scripts are in standard base64,
+/// arguments are in the format of printed evaluation stack, limits are `null` unless overridden:
+/// `{"entry": "...", "callees": ["...", ...], "args": [{"type": "Integer", "value": "1"}, ...], "limits": {...}}`.
+impl HasTargetBytes for ProgramInput {
+    fn target_bytes(&self) -> libafl_bolts::prelude::OwnedSlice<'_, u8> {
+        if self.is_plain_script() {
+            return self.entry.target_bytes();
+        }
+        json!({
+            "entry": self.entry.as_standard_base64(),
+            "callees": self.callees.iter().map(ByteCodeInput::as_standard_base64).collect::<Vec<_>>(),
+            "args": self.stack_items(),
+            "limits": self.limits,
+        })
+        .to_string()
+        .into_bytes()
+        .into()
+    }
+}
+
+/// Byte-level mutations change the entry script.
+impl HasMutatorBytes for ProgramInput {
+    fn bytes(&self) -> &[u8] {
+        self.entry.bytes()
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self.entry.bytes_mut()
+    }
+
+    fn resize(&mut self, new_len: usize, value: u8) {
+        self.entry.resize(new_len, value);
+    }
+
+    fn extend<'a, I: IntoIterator<Item = &'a u8>>(&mut self, iter: I) {
+        self.entry.extend(iter);
+    }
+
+    fn splice<R, I>(&mut self, range: R, replace_with: I) -> std::vec::Splice<'_, I::IntoIter>
+    where
+        R: std::ops::RangeBounds<usize>,
+        I: IntoIterator<Item = u8>,
+    {
+        self.entry.splice(range, replace_with)
+    }
+
+    fn drain<R>(&mut self, range: R) -> std::vec::Drain<'_, u8>
+    where
+        R: std::ops::RangeBounds<usize>,
+    {
+        self.entry.drain(range)
+    }
+}
+
+impl HasLen for ProgramInput {
+    fn len(&self) -> usize {
+        self.scripts().map(HasLen::len).sum()
+    }
+}
+
+impl Input for ProgramInput {
+    /// Queue of campaigns made before programs were introduced has plain scripts.
+    fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        match postcard::from_bytes(&fs::read(path.as_ref())?) {
+            Ok(input) => Ok(input),
+            Err(_) => Ok(ByteCodeInput::from_file(path)?.into()),
+        }
+    }
+
+    fn generate_name(&self, id: Option<libafl::prelude::CorpusId>) -> String {
+        let name = self.entry.generate_name(id);
+        if self.is_plain_script() {
+            return name;
+        }
+        let mut callees: Vec<u8> = self
+            .callees
+            .iter()
+            .flat_map(|callee| {
+                [
+                    &(callee.opcodes.len() as u64).to_le_bytes()[..],
+                    &callee.opcodes,
+                ]
+                .concat()
+            })
+            .collect();
+        if !self.args.is_empty() {
+            callees.extend(json!(self.stack_items()).to_string().bytes());
+        }
+        if let Some(limits) = &self.limits {
+            callees.extend(json!(limits).to_string().bytes());
+        }
+        format!(
+            "{}-{:016x}",
+            &name[..name.len().min(MAX_NAME_LEN - 17)],
+            hash_std(&callees)
+        )
     }
 }
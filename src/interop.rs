@@ -0,0 +1,85 @@
+use sha2::{Digest, Sha256};
+
+use crate::generator::Arg;
+
+/// Groups of interop services that can be enabled in engine mode.
+pub const GROUPS: &[&str] = &["Runtime", "Storage", "Crypto", "Contract"];
+
+/// Interop service called by `SYSCALL`.
+#[derive(Debug)]
+pub struct Syscall {
+    pub name: &'static str,
+    /// Arguments from the deepest stack item to the top one, like in [`crate::generator::signatures`].
+    pub args: &'static [Arg],
+}
+
+impl Syscall {
+    /// `SYSCALL` operand: first 4 bytes of SHA-256 of the name.
+    pub fn hash(&self) -> [u8; 4] {
+        Sha256::digest(self.name.as_bytes())[..4]
+            .try_into()
+            .unwrap()
+    }
+
+    /// `System.Runtime.Log` is in `Runtime` group.
+    pub fn group(&self) -> &'static str {
+        self.name.split('.').nth(1).unwrap()
+    }
+}
+
+macro_rules! syscalls {
+    ($($name:literal => [$($arg:ident),*]),* $(,)?) => {
+        &[$(Syscall { name: $name, args: &[$(Arg::$arg),*] }),*]
+    };
+}
+
+/// Interop services of the supported groups.
+pub const SYSCALLS: &[Syscall] = syscalls![
+    "System.Runtime.Platform" => [],
+    "System.Runtime.GetNetwork" => [],
+    "System.Runtime.GetAddressVersion" => [],
+    "System.Runtime.GetTrigger" => [],
+    "System.Runtime.GetTime" => [],
+    "System.Runtime.GetScriptContainer" => [],
+    "System.Runtime.GetExecutingScriptHash" => [],
+    "System.Runtime.GetCallingScriptHash" => [],
+    "System.Runtime.GetEntryScriptHash" => [],
+    "System.Runtime.LoadScript" => [Array, Small, Bytes],
+    "System.Runtime.CheckWitness" => [Bytes],
+    "System.Runtime.GetInvocationCounter" => [],
+    "System.Runtime.GetRandom" => [],
+    "System.Runtime.Log" => [Bytes],
+    "System.Runtime.Notify" => [Array, Bytes],
+    "System.Runtime.GetNotifications" => [Any],
+    "System.Runtime.GasLeft" => [],
+    "System.Runtime.BurnGas" => [Int],
+    "System.Runtime.CurrentSigners" => [],
+    "System.Storage.GetContext" => [],
+    "System.Storage.GetReadOnlyContext" => [],
+    "System.Storage.AsReadOnly" => [Context],
+    "System.Storage.Get" => [Bytes, Context],
+    "System.Storage.Find" => [Small, Bytes, Context],
+    "System.Storage.Put" => [Bytes, Bytes, Context],
+    "System.Storage.Delete" => [Bytes, Context],
+    "System.Crypto.CheckSig" => [Bytes, Bytes],
+    "System.Crypto.CheckMultisig" => [Array, Array],
+    "System.Contract.Call" => [Array, Small, Bytes, Bytes],
+    "System.Contract.CallNative" => [Small],
+    "System.Contract.GetCallFlags" => [],
+    "System.Contract.CreateStandardAccount" => [Bytes],
+    "System.Contract.CreateMultisigAccount" => [Array, Small],
+    "System.Contract.NativeOnPersist" => [],
+    "System.Contract.NativePostPersist" => [],
+];
+
+/// Interop services of given groups.
+pub fn in_groups(groups: &[String]) -> Vec<&'static Syscall> {
+    SYSCALLS
+        .iter()
+        .filter(|syscall| groups.iter().any(|group| group == syscall.group()))
+        .collect()
+}
+
+pub fn by_name(name: &str) -> Option<&'static Syscall> {
+    SYSCALLS.iter().find(|syscall| syscall.name == name)
+}
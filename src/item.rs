@@ -0,0 +1,344 @@
+use base64::prelude::*;
+use libafl_bolts::rands::Rand;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    dictionary,
+    output::{MapEntry, StackItem},
+};
+
+/// `MaxKeySize` of map keys.
+pub const MAX_KEY_SIZE: usize = 64;
+
+/// Compound items are not nested deeper than this.
+const MAX_DEPTH: usize = 3;
+
+/// Number of elements in generated compound item.
+const MAX_ELEMENTS: usize = 4;
+
+/// Codes of stack item types (`StackItemType`), operands of ISTYPE, CONVERT and NEWARRAY_T.
+pub const ANY: u8 = 0x00;
+pub const POINTER: u8 = 0x10;
+pub const BOOLEAN: u8 = 0x20;
+pub const INTEGER: u8 = 0x21;
+pub const BYTESTRING: u8 = 0x28;
+pub const BUFFER: u8 = 0x30;
+pub const ARRAY: u8 = 0x40;
+pub const STRUCT: u8 = 0x41;
+pub const MAP: u8 = 0x48;
+pub const INTEROP_INTERFACE: u8 = 0x60;
+
+/// All defined type codes, other operands are invalid.
+pub const TYPES: &[u8] = &[
+    ANY,
+    POINTER,
+    BOOLEAN,
+    INTEGER,
+    BYTESTRING,
+    BUFFER,
+    ARRAY,
+    STRUCT,
+    MAP,
+    INTEROP_INTERFACE,
+];
+
+/// Types of items scripts can make, without `Any`, `Pointer` and `InteropInterface` that type operands
+/// are mostly rejected with.
+pub const VALUE_TYPES: &[u8] = &[BOOLEAN, INTEGER, BYTESTRING, BUFFER, ARRAY, STRUCT, MAP];
+
+/// Typed stack item that harnesses push before running the script.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Null,
+    Boolean(bool),
+    /// Little-endian two's complement, like in PUSHINT operands.
+    Integer(Vec<u8>),
+    ByteString(Vec<u8>),
+    Buffer(Vec<u8>),
+    Array(Vec<Item>),
+    Struct(Vec<Item>),
+    Map(Vec<(Item, Item)>),
+}
+
+impl Item {
+    /// Item in the format harnesses use to print evaluation stack.
+    pub fn to_stack_item(&self) -> StackItem {
+        let (itype, ivalue) = match self {
+            Item::Null => ("Any", Value::Null),
+            Item::Boolean(value) => ("Boolean", json!(value)),
+            Item::Integer(value) => ("Integer", json!(to_decimal(value))),
+            Item::ByteString(value) => ("ByteString", json!(BASE64_STANDARD.encode(value))),
+            Item::Buffer(value) => ("Buffer", json!(BASE64_STANDARD.encode(value))),
+            Item::Array(items) => ("Array", items_to_json(items)),
+            Item::Struct(items) => ("Struct", items_to_json(items)),
+            Item::Map(pairs) => (
+                "Map",
+                pairs
+                    .iter()
+                    .map(|(key, value)| json!({"key": key.to_stack_item(), "value": value.to_stack_item()}))
+                    .collect(),
+            ),
+        };
+        StackItem {
+            itype: String::from(itype),
+            ivalue,
+            id: None,
+        }
+    }
+
+    pub fn from_stack_item(item: &StackItem) -> Result<Item, String> {
+        let invalid = || format!("invalid {} value: {}", item.itype, item.ivalue);
+        let bytes = || {
+            item.ivalue
+                .as_str()
+                .and_then(|value| BASE64_STANDARD.decode(value).ok())
+                .ok_or_else(invalid)
+        };
+        let items = || -> Result<Vec<Item>, String> {
+            let items: Vec<StackItem> =
+                serde_json::from_value(item.ivalue.clone()).map_err(|_| invalid())?;
+            items.iter().map(Item::from_stack_item).collect()
+        };
+        Ok(match item.itype.as_str() {
+            "Any" => Item::Null,
+            "Boolean" => Item::Boolean(item.ivalue.as_bool().ok_or_else(invalid)?),
+            "Integer" => Item::Integer(
+                item.ivalue
+                    .as_str()
+                    .and_then(from_decimal)
+                    .ok_or_else(invalid)?,
+            ),
+            "ByteString" => Item::ByteString(bytes()?),
+            "Buffer" => Item::Buffer(bytes()?),
+            "Array" => Item::Array(items()?),
+            "Struct" => Item::Struct(items()?),
+            "Map" => {
+                let pairs: Vec<MapEntry> =
+                    serde_json::from_value(item.ivalue.clone()).map_err(|_| invalid())?;
+                Item::Map(
+                    pairs
+                        .iter()
+                        .map(|pair| {
+                            Ok((
+                                Item::from_stack_item(&pair.key)?,
+                                Item::from_stack_item(&pair.value)?,
+                            ))
+                        })
+                        .collect::<Result<_, String>>()?,
+                )
+            }
+            other => return Err(format!("unsupported item type {other}")),
+        })
+    }
+
+    /// Number of items that can be replaced inside (map keys are not counted).
+    fn children(&self) -> usize {
+        match self {
+            Item::Array(items) | Item::Struct(items) => items.len(),
+            Item::Map(pairs) => pairs.len(),
+            _ => 0,
+        }
+    }
+
+    /// This item or one of the items nested in it.
+    pub fn pick_mut<R: Rand>(&mut self, rand: &mut R) -> &mut Item {
+        let children = self.children();
+        if children == 0 || rand.coinflip(0.5) {
+            return self;
+        }
+        let i = rand.below(children);
+        match self {
+            Item::Array(items) | Item::Struct(items) => items[i].pick_mut(rand),
+            Item::Map(pairs) => pairs[i].1.pick_mut(rand),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn items_to_json(items: &[Item]) -> Value {
+    items
+        .iter()
+        .map(|item| json!(item.to_stack_item()))
+        .collect()
+}
+
+/// Decimal representation of little-endian two's complement integer.
+fn to_decimal(value: &[u8]) -> String {
+    let negative = value.last().is_some_and(|byte| *byte >= 0x80);
+    let mut magnitude: Vec<u8> = value.iter().rev().copied().collect();
+    if negative {
+        for byte in magnitude.iter_mut() {
+            *byte = !*byte;
+        }
+        for byte in magnitude.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+    let mut digits = Vec::new();
+    while magnitude.iter().any(|byte| *byte != 0) {
+        let mut rem = 0u16;
+        for byte in magnitude.iter_mut() {
+            let cur = rem * 256 + *byte as u16;
+            *byte = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    if negative {
+        digits.push(b'-');
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Little-endian two's complement of decimal integer, `None` if it's not an integer or doesn't fit into 32 bytes.
+pub fn from_decimal(value: &str) -> Option<Vec<u8>> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    if digits.is_empty() || !digits.bytes().all(|d| d.is_ascii_digit()) {
+        return None;
+    }
+    let mut result = vec![0u8; dictionary::MAX_INTEGER_SIZE + 1];
+    for digit in digits.bytes() {
+        let mut carry = (digit - b'0') as u16;
+        for byte in result.iter_mut() {
+            let cur = *byte as u16 * 10 + carry;
+            *byte = cur as u8;
+            carry = cur >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    if negative {
+        for byte in result.iter_mut() {
+            *byte = !*byte;
+        }
+        dictionary::add(&mut result, 1);
+    }
+    let result = dictionary::minimize(result);
+    (result.len() <= dictionary::MAX_INTEGER_SIZE).then_some(result)
+}
+
+/// Random items made of interesting values.
+#[derive(Debug, Clone)]
+pub struct ItemGenerator {
+    integers: Vec<Vec<u8>>,
+    byte_strings: Vec<Vec<u8>>,
+}
+
+impl Default for ItemGenerator {
+    fn default() -> Self {
+        Self {
+            integers: dictionary::integers(),
+            byte_strings: dictionary::byte_strings(),
+        }
+    }
+}
+
+impl ItemGenerator {
+    pub fn item<R: Rand>(&self, rand: &mut R, depth: usize) -> Item {
+        let kinds = if depth < MAX_DEPTH { 8 } else { 5 };
+        match rand.below(kinds) {
+            0 => Item::Null,
+            1 => Item::Boolean(rand.coinflip(0.5)),
+            2 => Item::Integer(rand.choose(&self.integers).unwrap().clone()),
+            3 => Item::ByteString(rand.choose(&self.byte_strings).unwrap().clone()),
+            4 => Item::Buffer(rand.choose(&self.byte_strings).unwrap().clone()),
+            5 => Item::Array(self.items(rand, depth + 1)),
+            6 => Item::Struct(self.items(rand, depth + 1)),
+            _ => Item::Map(
+                (0..rand.below(MAX_ELEMENTS + 1))
+                    .map(|_| (self.key(rand), self.item(rand, depth + 1)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn items<R: Rand>(&self, rand: &mut R, depth: usize) -> Vec<Item> {
+        (0..rand.below(MAX_ELEMENTS + 1))
+            .map(|_| self.item(rand, depth))
+            .collect()
+    }
+
+    /// Map keys must be primitive.
+    fn key<R: Rand>(&self, rand: &mut R) -> Item {
+        match rand.below(3) {
+            0 => Item::Boolean(rand.coinflip(0.5)),
+            1 => Item::Integer(rand.choose(&self.integers).unwrap().clone()),
+            _ => Item::ByteString(rand.choose(&self.byte_strings).unwrap().clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: &str =
+        "57896044618658097711785492504343953926634992332820282019728792003956564819967";
+    const MIN: &str =
+        "-57896044618658097711785492504343953926634992332820282019728792003956564819968";
+
+    #[test]
+    fn decimal() {
+        for (decimal, bytes) in [
+            ("0", &[0x00][..]),
+            ("1", &[0x01]),
+            ("-1", &[0xff]),
+            ("127", &[0x7f]),
+            ("128", &[0x80, 0x00]),
+            ("-128", &[0x80]),
+            ("-129", &[0x7f, 0xff]),
+            ("256", &[0x00, 0x01]),
+        ] {
+            assert_eq!(from_decimal(decimal).unwrap(), bytes, "{decimal}");
+            assert_eq!(to_decimal(bytes), decimal);
+        }
+        assert_eq!(from_decimal("007").unwrap(), [0x07]);
+        assert_eq!(from_decimal("-0").unwrap(), [0x00]);
+        // Redundant sign bytes are allowed in operands.
+        assert_eq!(to_decimal(&[0xff, 0xff]), "-1");
+        assert_eq!(to_decimal(&[]), "0");
+    }
+
+    #[test]
+    fn decimal_bounds() {
+        let max = from_decimal(MAX).unwrap();
+        assert_eq!(max, [vec![0xff; 31], vec![0x7f]].concat());
+        assert_eq!(to_decimal(&max), MAX);
+        let min = from_decimal(MIN).unwrap();
+        assert_eq!(min, [vec![0x00; 31], vec![0x80]].concat());
+        assert_eq!(to_decimal(&min), MIN);
+        // One past the bounds needs 33 bytes.
+        assert_eq!(
+            from_decimal(
+                "57896044618658097711785492504343953926634992332820282019728792003956564819968"
+            ),
+            None
+        );
+        assert_eq!(
+            from_decimal(
+                "-57896044618658097711785492504343953926634992332820282019728792003956564819969"
+            ),
+            None
+        );
+        assert_eq!(from_decimal(&"9".repeat(100)), None);
+    }
+
+    #[test]
+    fn invalid_decimal() {
+        for value in ["", "-", "+1", "1a", " 1", "1.0", "--1", "0x10"] {
+            assert_eq!(from_decimal(value), None, "{value}");
+        }
+    }
+}
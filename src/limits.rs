@@ -0,0 +1,72 @@
+use libafl_bolts::rands::Rand;
+use serde::{Deserialize, Serialize};
+
+/// Execution limits both harnesses run scripts with, defaults are the ones of Neo N3 VM.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Limits {
+    pub max_shift: u64,
+    pub max_stack_size: u64,
+    pub max_item_size: u64,
+    pub max_comparable_size: u64,
+    pub max_invocation_stack_size: u64,
+    pub max_try_nesting_depth: u64,
+    /// In datoshi, only used in engine mode.
+    pub gas_limit: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_shift: 256,
+            max_stack_size: 2048,
+            max_item_size: 131070,
+            max_comparable_size: 65536,
+            max_invocation_stack_size: 1024,
+            max_try_nesting_depth: 16,
+            gas_limit: 20_0000_0000,
+        }
+    }
+}
+
+impl Limits {
+    fn values(&self) -> [u64; 7] {
+        [
+            self.max_shift,
+            self.max_stack_size,
+            self.max_item_size,
+            self.max_comparable_size,
+            self.max_invocation_stack_size,
+            self.max_try_nesting_depth,
+            self.gas_limit,
+        ]
+    }
+
+    fn values_mut(&mut self) -> [&mut u64; 7] {
+        [
+            &mut self.max_shift,
+            &mut self.max_stack_size,
+            &mut self.max_item_size,
+            &mut self.max_comparable_size,
+            &mut self.max_invocation_stack_size,
+            &mut self.max_try_nesting_depth,
+            &mut self.gas_limit,
+        ]
+    }
+
+    /// Sets one of the limits to a value near the edges or to a random one up to twice its value in `base`.
+    pub fn mutate<R: Rand>(&mut self, rand: &mut R, base: &Limits) {
+        let i = rand.below(7);
+        let base = base.values()[i];
+        let value = match rand.below(7) {
+            0 => 0,
+            1 => 1,
+            2 => base.saturating_sub(1),
+            3 => base.saturating_add(1),
+            4 => base / 2,
+            5 => base.saturating_mul(2),
+            _ => rand.below(base.saturating_mul(2).saturating_add(1) as usize) as u64,
+        };
+        *self.values_mut()[i] = value;
+    }
+}
@@ -1,200 +1,367 @@
+mod asm;
+mod campaign;
+mod config;
+mod corpus;
+mod coverage;
+mod dictionary;
+mod exception;
 mod feedback;
+mod generator;
+mod harness;
 mod input;
+mod interop;
+mod item;
+mod limits;
+mod nef;
 mod objective;
 mod observer;
+mod opcode;
+mod oracle;
 mod output;
+mod program;
+mod reference;
+mod scheduler;
+mod script;
+mod stage;
 
-use std::{env, path::PathBuf};
+use std::{path::Path, time::Duration};
 
 use base64::prelude::*;
-use clap::Parser;
-use feedback::{go_cover::GoCoverFeedback, type_state::TypeStateFeedback};
-use input::ByteCodeInput;
+use config::{Args, Command};
+use dictionary::InterestingValueMutator;
+use exception::TryMutator;
+use feedback::{
+    go_cover::GoCoverFeedback, hit_count::HitCountFeedback, last_op::LastOpFeedback,
+    type_state::TypeStateFeedback, FeedbackKind, Novelty,
+};
+use generator::{GenerateMutator, ScriptGenerator};
+use harness::Harness;
+use input::{ByteCodeInput, ProgramInput};
 use libafl::prelude::*;
 use libafl_bolts::{
-    core_affinity::Cores,
-    current_nanos,
+    ownedref::OwnedMutSlice,
     prelude::CoreId,
     rands::StdRand,
     shmem::{ShMemProvider, StdShMemProvider},
-    tuples::{tuple_list, Handled},
+    tuples::{tuple_list, Handled, Merge},
 };
-use observer::GoCoverObserver;
-use rand::seq::SliceRandom;
-
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(
-        long,
-        help = "Report error if VMs execution ends with different status. Can result in many false positives!",
-        name = "DETECT_STATUS_DIFFERENCE",
-        default_value_t = false
-    )]
-    detect_status_diff: std::primitive::bool,
-
-    #[arg(
-        short = 'c',
-        long,
-        help = "Report error if one of VMs crashes.",
-        name = "DETECT_CRASH_DIFFERENCE",
-        default_value_t = true
-    )]
-    detect_crash_diff: std::primitive::bool,
-
-    #[arg(
-        short = 'f',
-        long,
-        help = "Read initial corpus from file",
-        name = "READ_CORPUS_FROM_FILE",
-        default_value_t = true
-    )]
-    read_corpus_from_file: std::primitive::bool,
-
-    #[arg(
-        short = 's',
-        long,
-        help = "Spread inputs from initial corpus between cores",
-        name = "SPREAD_CORPUS",
-        default_value_t = true
-    )]
-    spread_corpus: std::primitive::bool,
-
-    #[arg(
-        short = 'p',
-        long,
-        help = "Choose the broker TCP port",
-        name = "PORT",
-        default_value = "7777"
-    )]
-    broker_port: u16,
-
-    #[arg(
-        long,
-        value_parser = Cores::from_cmdline,
-        help = "Spawn a client in each of the provided cores. Broker runs in the 0th core. 'all' to select all available cores. eg: '1,2-4,6' selects the cores 1,2,3,4,6.",
-        name = "CORES"
-    )]
-    cores: Cores,
-}
+use observer::{GoCoverObserver, GO_COVER_MAP_SIZE, GO_HITCOUNT_OBSERVER};
+use program::ProgramMutator;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use scheduler::TargetOpcodeScheduler;
+use script::{CrossoverMutator, InstructionDeleteMutator, InstructionInsertMutator};
+use stage::{dump_corpus::DumpCorpusStage, novelty::NoveltyStage};
 
 fn main() {
-    let mut rng = rand::thread_rng();
-    let args = Args::parse();
-
-    let shmem_provider = StdShMemProvider::new().expect("Failed to init shared memory");
-    let monitor = MultiMonitor::new(|s| println!("{s}"));
+    let args = Args::parse_validated();
 
-    let temp_dir = env::temp_dir().join("N3onDiff");
-    std::fs::remove_dir_all(temp_dir.as_path()).unwrap_or(());
-    std::fs::create_dir(temp_dir.as_path()).unwrap_or(());
+    if let Some(Command::Export { output, found_by }) = &args.command {
+        let Some(campaign_dir) = args.previous_campaign() else {
+            eprintln!("No campaign to export.");
+            std::process::exit(1);
+        };
+        match corpus::export_queues(campaign_dir.as_path(), output.as_deref(), *found_by) {
+            Ok(count) => eprintln!("Exported {count} inputs from {}.", campaign_dir.display()),
+            Err(err) => {
+                eprintln!("Failed to export corpus: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Coverage) = &args.command {
+        let Some(campaign_dir) = args.previous_campaign() else {
+            eprintln!("No campaign to report coverage for.");
+            std::process::exit(1);
+        };
+        report_coverage(campaign_dir.as_path());
+        return;
+    }
+    let config = args.config();
+    if args.dry_run {
+        println!("{}", serde_json::to_string_pretty(&config).unwrap());
+        return;
+    }
+    let cores = config.cores.clone();
+    let target_codes = config.target_codes();
+    let ungenerated = ScriptGenerator::ungenerated(&target_codes, !config.syscalls().is_empty());
+    if !ungenerated.is_empty() {
+        let names: Vec<&str> = ungenerated
+            .iter()
+            .filter_map(|code| opcode::by_code(*code))
+            .map(|info| info.name)
+            .collect();
+        let fallback = if ungenerated.len() == target_codes.len() {
+            ", generating all opcodes instead"
+        } else {
+            ""
+        };
+        eprintln!(
+            "Warning: target opcodes {} can't be generated{fallback}.",
+            names.join(",")
+        );
+    }
+    let harness_env = config.harness_env();
+    let limits_profile = config.limits_profile();
+    let limits = harness::limits_arg(limits_profile.as_ref());
 
     let mut corpus_from_file = Vec::new();
-    if args.read_corpus_from_file {
-        for line in std::fs::read_to_string("./corpus/corpus.txt")
-            .unwrap()
-            .lines()
-        {
-            corpus_from_file.push(Testcase::new(ByteCodeInput {
-                opcodes: BASE64_STANDARD.decode(line).unwrap(),
-            }));
+    if config.read_corpus_from_file {
+        match corpus::load_seeds(&config.corpus) {
+            Ok(seeds) => corpus_from_file.extend(seeds.into_iter().map(Testcase::new)),
+            Err(err) => {
+                eprintln!("Failed to load corpus: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    corpus_from_file.shuffle(&mut StdRng::seed_from_u64(config.seed));
+
+    // Programs are passed as JSON if seeds or mutations give them callees, arguments or limits.
+    let programs = config.max_callees > 0
+        || config.max_args > 0
+        || config.fuzz_limits
+        || corpus_from_file.iter().any(|testcase| {
+            testcase
+                .input()
+                .as_ref()
+                .is_some_and(|input| !input.is_plain_script())
+        });
+    for path in [harness::NEO_GO, harness::NEO_SHARP] {
+        let harness = Harness {
+            path,
+            env: &harness_env,
+            limits: limits_profile.as_ref(),
+        };
+        if let Err(err) = harness.check(programs, config.compare_aliasing, &config.syscalls()) {
+            eprintln!("Harness {path} doesn't support the protocol: {err}.");
+            std::process::exit(1);
         }
     }
-    corpus_from_file.shuffle(&mut rng);
+
+    // Resumed campaign runs with the config it recorded when it started (see `Args::config`), so it is not saved again.
+    let campaign_dir = if config.resume {
+        match args.previous_campaign() {
+            Some(dir) => dir,
+            None => {
+                eprintln!("No campaign to resume in {}.", config.out.display());
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let dir =
+            campaign::create(config.out.as_path()).expect("Failed to create campaign directory");
+        std::fs::write(
+            dir.join("config.json"),
+            serde_json::to_string_pretty(&config).unwrap(),
+        )
+        .expect("Failed to save config");
+        dir
+    };
+    println!("Campaign directory: {}", campaign_dir.display());
+    println!("Using seed {}.", config.seed);
+
+    let shmem_provider = StdShMemProvider::new().expect("Failed to init shared memory");
+    let monitor = OnDiskTomlMonitor::new(
+        campaign_dir.join("stats.toml"),
+        MultiMonitor::new(|s| println!("{s}")),
+    );
+
     let testcases_chunks: Vec<Vec<_>> = corpus_from_file
-        .chunks(corpus_from_file.len() / args.cores.ids.len())
+        .chunks(corpus_from_file.len().div_ceil(cores.ids.len()).max(1))
         .map(|x| x.to_vec())
         .collect();
 
-    let mut run_client = |_state: Option<_>, mut restarting_mgr, core_id: CoreId| {
+    let mut run_client = |state: Option<_>,
+                          mut restarting_mgr: LlmpRestartingEventManager<_, _, _>,
+                          core_id: CoreId| {
         let neogo_stdout_observer = StdOutObserver::new("neogo-stdout-observer");
         let neosharp_stdout_observer = StdOutObserver::new("neosharp-stdout-observer");
 
         let mut objective = objective::DiffStdOutObjective::new(
             neogo_stdout_observer.handle(),
             neosharp_stdout_observer.handle(),
-            args.detect_status_diff,
-            args.detect_crash_diff,
+            &config,
         );
 
-        let core_temp_dir = temp_dir.join(core_id.0.to_string());
-        std::fs::create_dir(core_temp_dir.as_path()).unwrap_or(());
+        let core_dir = campaign_dir.join(core_id.0.to_string());
+        std::fs::create_dir(core_dir.as_path()).unwrap_or(());
 
-        let go_cover_dir = core_temp_dir.join("go-cover");
+        let go_cover_dir = core_dir.join("go-cover");
         std::fs::create_dir(go_cover_dir.as_path()).unwrap_or(());
-        let go_cover_merged_dir = core_temp_dir.join("go-cover-merged");
+        let go_cover_merged_dir = core_dir.join("go-cover-merged");
         std::fs::create_dir(go_cover_merged_dir.as_path()).unwrap_or(());
-        let go_cover_tmp_dir = core_temp_dir.join("go-cover-tmp");
+        let go_cover_tmp_dir = core_dir.join("go-cover-tmp");
         std::fs::create_dir(go_cover_tmp_dir.as_path()).unwrap_or(());
+        // Both observers point to the same map: the first one writes hit counts, the second one puts them into buckets.
+        // SAFETY: the map is leaked, so it lives as long as the client process (and the observers that live in the
+        // executor until the process exits). Both observers keep only raw pointers to it and create slices just for
+        // the duration of their own `pre_exec`/`post_exec` calls, which the executor makes one at a time in tuple
+        // order: `GoCoverObserver` writes counts, then `HitcountsMapObserver` classifies them (and clears the map
+        // before the next run). No reference to the map outlives a call, so the two never alias mutably.
+        let go_hitcounts = Box::leak(vec![0u8; GO_COVER_MAP_SIZE].into_boxed_slice()).as_mut_ptr();
+        let go_hitcount_observer = HitcountsMapObserver::new(unsafe {
+            StdMapObserver::from_mut_ptr(GO_HITCOUNT_OBSERVER, go_hitcounts, GO_COVER_MAP_SIZE)
+        });
         let go_cover_observer = GoCoverObserver::new(
+            unsafe { OwnedMutSlice::from_raw_parts_mut(go_hitcounts, GO_COVER_MAP_SIZE) },
+            config.go_cover_filter.clone(),
             go_cover_dir.clone().into_boxed_path(),
             go_cover_merged_dir.into_boxed_path(),
             go_cover_tmp_dir.into_boxed_path(),
         );
 
         let mut feedback = feedback_or!(
-            TypeStateFeedback::new(vec![
+            TypeStateFeedback::new(
+                vec![
+                    neogo_stdout_observer.handle(),
+                    neosharp_stdout_observer.handle(),
+                ],
+                core_dir
+                    .join(FeedbackKind::TypeState.state_file())
+                    .into_boxed_path(),
+            ),
+            GoCoverFeedback::new(
+                go_cover_observer.handle(),
+                core_dir
+                    .join(FeedbackKind::GoCover.state_file())
+                    .into_boxed_path(),
+            ),
+            HitCountFeedback::new(
+                &go_hitcount_observer,
+                core_dir
+                    .join(FeedbackKind::HitCount.state_file())
+                    .into_boxed_path(),
+            ),
+            LastOpFeedback::new(
                 neogo_stdout_observer.handle(),
-                neosharp_stdout_observer.handle(),
-            ]),
-            GoCoverFeedback::new(go_cover_observer.handle())
+                target_codes.clone(),
+                core_dir
+                    .join(FeedbackKind::LastOp.state_file())
+                    .into_boxed_path(),
+            )
         );
 
         let neogo_executor = CommandExecutor::builder()
-            .program("./harness/neo-go")
+            .program(harness::NEO_GO)
             .env("GOCOVERDIR", go_cover_dir.as_path())
+            .envs(harness_env.clone())
             .arg_input_arg()
-            .arg("DUMMY")
+            .arg(&limits)
             .stdout_observer(neogo_stdout_observer.handle())
-            .build(tuple_list!(neogo_stdout_observer, go_cover_observer))
+            .build(tuple_list!(
+                neogo_stdout_observer,
+                go_cover_observer,
+                go_hitcount_observer
+            ))
             .unwrap();
 
         let neosharp_executor = CommandExecutor::builder()
-            .program("./harness/neo-sharp")
+            .program(harness::NEO_SHARP)
+            .envs(harness_env.clone())
             .arg_input_arg()
-            .arg("DUMMY")
+            .arg(&limits)
             .stdout_observer(neosharp_stdout_observer.handle())
             .build(tuple_list!(neosharp_stdout_observer))
             .unwrap();
 
         let mut executor = DiffExecutor::new(neogo_executor, neosharp_executor, ());
 
-        let mut state = StdState::new(
-            StdRand::with_seed(current_nanos()),
-            InMemoryCorpus::new(),
-            OnDiskCorpus::new(PathBuf::from("./crashes")).unwrap(),
-            &mut feedback,
-            &mut objective,
-        )
-        .unwrap();
+        let mut state = match state {
+            Some(state) => state,
+            None => {
+                let queue_dir = core_dir.join("queue");
+                let resumed = if config.resume {
+                    corpus::take_queue(queue_dir.as_path())?
+                } else {
+                    Vec::new()
+                };
 
-        let corpus = state.corpus_mut();
+                let mut state = StdState::new(
+                    StdRand::with_seed(config.core_seed(core_id)),
+                    InMemoryOnDiskCorpus::new(queue_dir.as_path())?,
+                    OnDiskCorpus::new(campaign_dir.join("crashes"))?,
+                    &mut feedback,
+                    &mut objective,
+                )?;
 
-        corpus
-            .add(Testcase::new(ByteCodeInput {
-                opcodes: BASE64_STANDARD.decode("DAxIZWxsbyB3b3JsZCE=").unwrap(),
-            }))
-            .unwrap();
+                state.add_metadata(dictionary::tokens());
+
+                let corpus = state.corpus_mut();
+
+                if !resumed.is_empty() {
+                    println!("Resuming with {} inputs from queue.", resumed.len());
+                    // Feedbacks restored their state from disk, so inputs they found before are not judged again.
+                    for tc in resumed {
+                        corpus.add(tc)?;
+                    }
+                    Novelty::from_state(&state).fire(&mut state, &mut restarting_mgr)?;
+                } else {
+                    corpus.add(Testcase::new(ProgramInput::from(ByteCodeInput {
+                        opcodes: BASE64_STANDARD.decode("DAxIZWxsbyB3b3JsZCE=").unwrap(),
+                    })))?;
 
-        if args.spread_corpus {
-            for cid in 0..args.cores.ids.len() {
-                if args.cores.ids[cid] == core_id {
-                    for tc in testcases_chunks[cid].clone() {
-                        corpus.add(tc).unwrap();
+                    let mut generator =
+                        ScriptGenerator::targeting(&target_codes).with_syscalls(config.syscalls());
+                    for _ in 0..config.generate_seeds {
+                        let input = generator.generate(&mut state)?;
+                        state
+                            .corpus_mut()
+                            .add(Testcase::new(ProgramInput::from(input)))?;
+                    }
+                    let corpus = state.corpus_mut();
+
+                    if config.spread_corpus {
+                        let cid = cores.ids.iter().position(|id| *id == core_id);
+                        if let Some(chunk) = cid.and_then(|cid| testcases_chunks.get(cid)) {
+                            for tc in chunk.clone() {
+                                corpus.add(tc)?;
+                            }
+                        }
+                    } else {
+                        for tc in corpus_from_file.iter() {
+                            corpus.add(tc.clone())?;
+                        }
                     }
-                    break;
                 }
+
+                corpus::finish_take_queue(queue_dir.as_path())?;
+
+                state
             }
-        } else {
-            for tc in corpus_from_file.iter() {
-                corpus.add(tc.clone()).unwrap();
-            }
-        }
+        };
+
+        // Restored state already has everything received before restart, handler only needs to be added again.
+        restarting_mgr.add_custom_buf_handler(Box::new(Novelty::handle));
 
-        let scheduler = QueueScheduler::new();
+        let scheduler = TargetOpcodeScheduler::new(QueueScheduler::new(), target_codes.clone());
 
-        let mutator = StdScheduledMutator::new(havoc_mutations());
-        let mut stages = tuple_list!(StdMutationalStage::new(mutator));
+        let script_mutator =
+            StdScheduledMutator::new(havoc_mutations().merge(tokens_mutations()).merge(
+                tuple_list!(
+                    GenerateMutator::new(
+                        ScriptGenerator::targeting(&target_codes).with_syscalls(config.syscalls())
+                    ),
+                    InterestingValueMutator::default(),
+                    InstructionInsertMutator,
+                    InstructionDeleteMutator,
+                    CrossoverMutator,
+                    TryMutator::default()
+                ),
+            ));
+        let mut mutator = ProgramMutator::new(script_mutator, config.max_callees, config.max_args);
+        if config.fuzz_limits {
+            mutator = mutator.with_limits(limits_profile.unwrap_or_default());
+        }
+        let mut stages = tuple_list!(
+            StdMutationalStage::new(mutator),
+            NoveltyStage::new(core_dir.clone().into_boxed_path()),
+            DumpCorpusStage::new(
+                core_dir.join("corpus.txt").into_boxed_path(),
+                config.dump_corpus_interval.map(Duration::from_secs),
+                config.dump_found_by,
+            )
+        );
 
         let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
 
@@ -213,16 +380,29 @@ fn main() {
         .configuration(EventConfig::from_name("default"))
         .monitor(monitor)
         .run_client(&mut run_client)
-        .cores(&args.cores)
-        .broker_port(args.broker_port)
+        .cores(&cores)
+        .broker_port(config.broker_port)
         .build()
         .launch()
     {
         Ok(()) => (),
         Err(Error::ShuttingDown) => {
             println!("Fuzzing stopped by user. Good bye.");
-            println!("NOTE: coverage data for each client can be found in {} directory.", temp_dir.display());
-        },
+            report_coverage(campaign_dir.as_path());
+        }
         Err(err) => panic!("Failed to run launcher: {err:?}"),
     }
 }
+
+fn report_coverage(campaign_dir: &Path) {
+    println!("Merging coverage...");
+    match coverage::report(campaign_dir) {
+        Ok(coverage_dir) => {
+            if let Ok(files) = std::fs::read_to_string(coverage_dir.join("files.txt")) {
+                print!("{files}");
+            }
+            println!("Coverage report saved to {}.", coverage_dir.display());
+        }
+        Err(err) => eprintln!("Failed to merge coverage: {err}"),
+    }
+}
@@ -0,0 +1,284 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{item::Item, opcode::JMP_L};
+
+/// `NEF3` in little-endian.
+const MAGIC: u32 = 0x3346454E;
+const COMPILER_SIZE: usize = 64;
+const HASH_SIZE: usize = 20;
+
+/// Neo Executable Format file.
+#[derive(Debug, Clone)]
+pub struct Nef {
+    pub compiler: String,
+    pub tokens: Vec<MethodToken>,
+    pub script: Vec<u8>,
+}
+
+/// Static call of another contract's method, used by `CALLT`.
+#[derive(Debug, Clone)]
+pub struct MethodToken {
+    pub hash: [u8; HASH_SIZE],
+    pub method: String,
+}
+
+impl std::fmt::Display for MethodToken {
+    /// Contract hash is shown big-endian, like in Neo tools.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x")?;
+        for byte in self.hash.iter().rev() {
+            write!(f, "{byte:02x}")?;
+        }
+        write!(f, ".{}", self.method)
+    }
+}
+
+impl Nef {
+    pub fn parse(data: &[u8]) -> Result<Nef, String> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.u32()? != MAGIC {
+            return Err(String::from("wrong magic"));
+        }
+        let compiler = String::from_utf8_lossy(reader.bytes(COMPILER_SIZE)?)
+            .trim_end_matches('\0')
+            .to_string();
+        // source, reserved
+        reader.var_bytes()?;
+        reader.bytes(1)?;
+        let mut tokens = Vec::new();
+        for _ in 0..reader.var_int()? {
+            tokens.push(MethodToken {
+                hash: reader.bytes(HASH_SIZE)?.try_into().unwrap(),
+                method: String::from_utf8_lossy(reader.var_bytes()?).to_string(),
+            });
+            // parameters count, has return value, call flags
+            reader.bytes(2 + 1 + 1)?;
+        }
+        // reserved
+        reader.bytes(2)?;
+        let script = reader.var_bytes()?.to_vec();
+        let end = reader.pos;
+        if reader.u32()? != checksum(&data[..end]) {
+            return Err(String::from("wrong checksum"));
+        }
+        if script.is_empty() {
+            return Err(String::from("empty script"));
+        }
+        Ok(Nef {
+            compiler,
+            tokens,
+            script,
+        })
+    }
+}
+
+/// First 4 bytes of double SHA-256.
+fn checksum(data: &[u8]) -> u32 {
+    let hash = Sha256::digest(Sha256::digest(data));
+    u32::from_le_bytes(hash[..4].try_into().unwrap())
+}
+
+/// Part of contract manifest (`*.manifest.json`) describing its methods.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub abi: Abi,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Abi {
+    pub methods: Vec<Method>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Method {
+    pub name: String,
+    pub offset: usize,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Parameter {
+    #[serde(rename = "type")]
+    pub ptype: String,
+}
+
+impl Manifest {
+    pub fn parse(data: &[u8]) -> Result<Manifest, String> {
+        serde_json::from_slice(data).map_err(|err| format!("invalid manifest ({err})"))
+    }
+}
+
+impl Method {
+    /// Script that jumps to the method first, so that it starts at method offset.
+    /// `None` if the offset is outside of the script.
+    pub fn entry(&self, script: &[u8]) -> Option<Vec<u8>> {
+        if self.offset >= script.len() {
+            return None;
+        }
+        let jump = 5 + self.offset as i32;
+        let mut entry = vec![JMP_L];
+        entry.extend_from_slice(&jump.to_le_bytes());
+        entry.extend_from_slice(script);
+        Some(entry)
+    }
+
+    /// Default arguments of parameter types in the order they are pushed: `INITSLOT` takes the first one from the top.
+    pub fn args(&self) -> Vec<Item> {
+        self.parameters
+            .iter()
+            .rev()
+            .map(|param| match param.ptype.as_str() {
+                "Boolean" => Item::Boolean(false),
+                "Integer" => Item::Integer(Vec::new()),
+                "ByteArray" | "String" | "Signature" => Item::ByteString(Vec::new()),
+                "Hash160" => Item::ByteString(vec![0; HASH_SIZE]),
+                "Hash256" => Item::ByteString(vec![0; 32]),
+                "PublicKey" => Item::ByteString([&[0x02][..], &[0; 32]].concat()),
+                "Array" => Item::Array(Vec::new()),
+                "Map" => Item::Map(Vec::new()),
+                _ => Item::Null,
+            })
+            .collect()
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(format!("unexpected end of file at {}", self.pos))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn var_int(&mut self) -> Result<u64, String> {
+        Ok(match self.bytes(1)?[0] {
+            0xFD => u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()) as u64,
+            0xFE => u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as u64,
+            0xFF => u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()),
+            b => b as u64,
+        })
+    }
+
+    fn var_bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.var_int()?;
+        self.bytes(usize::try_from(len).map_err(|_| format!("length {len} is too big"))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::{PUSH1, RET};
+
+    /// NEF file with given tokens and script, valid checksum.
+    fn nef(tokens: &[(&[u8; HASH_SIZE], &str)], script: &[u8]) -> Vec<u8> {
+        let mut data = MAGIC.to_le_bytes().to_vec();
+        let mut compiler = b"neon-3.7".to_vec();
+        compiler.resize(COMPILER_SIZE, 0);
+        data.extend(compiler);
+        // source, reserved
+        data.extend([0, 0]);
+        data.push(tokens.len() as u8);
+        for (hash, method) in tokens {
+            data.extend(*hash);
+            data.push(method.len() as u8);
+            data.extend(method.as_bytes());
+            data.extend([0, 0, 1, 0x0f]);
+        }
+        data.extend([0, 0]);
+        data.push(script.len() as u8);
+        data.extend(script);
+        data.extend(checksum(&data).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn checksum_is_double_sha256() {
+        assert_eq!(checksum(b""), 0xe2e0f65d);
+        assert_eq!(checksum(b"NEF3"), 0x0826e932);
+    }
+
+    #[test]
+    fn parse() {
+        let mut hash = [0; HASH_SIZE];
+        hash[0] = 0xcf;
+        hash[HASH_SIZE - 1] = 0xd2;
+        let parsed = Nef::parse(&nef(&[(&hash, "transfer")], &[PUSH1, RET])).unwrap();
+        assert_eq!(parsed.compiler, "neon-3.7");
+        assert_eq!(parsed.script, [PUSH1, RET]);
+        assert_eq!(parsed.tokens.len(), 1);
+        assert_eq!(
+            parsed.tokens[0].to_string(),
+            format!("0xd2{}cf.transfer", "00".repeat(HASH_SIZE - 2))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let data = nef(&[], &[RET]);
+        assert!(Nef::parse(&data).is_ok());
+        let mut corrupted = data.clone();
+        corrupted[0] ^= 1;
+        assert_eq!(Nef::parse(&corrupted).unwrap_err(), "wrong magic");
+        let mut corrupted = data.clone();
+        let script = data.len() - 5;
+        corrupted[script] ^= 1;
+        assert_eq!(Nef::parse(&corrupted).unwrap_err(), "wrong checksum");
+        assert!(Nef::parse(&data[..data.len() - 1])
+            .unwrap_err()
+            .starts_with("unexpected end of file"));
+        assert_eq!(Nef::parse(&nef(&[], &[])).unwrap_err(), "empty script");
+    }
+
+    #[test]
+    fn var_int() {
+        let mut reader = Reader {
+            data: &[0xfc, 0xfd, 0x00, 0x01, 0xfe, 0x01, 0x00, 0x00, 0x00],
+            pos: 0,
+        };
+        assert_eq!(reader.var_int().unwrap(), 0xfc);
+        assert_eq!(reader.var_int().unwrap(), 0x100);
+        assert_eq!(reader.var_int().unwrap(), 1);
+        assert!(reader.var_int().is_err());
+        let mut reader = Reader {
+            data: &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            pos: 0,
+        };
+        assert!(reader.var_bytes().is_err());
+    }
+
+    #[test]
+    fn method_entry() {
+        let method = Method {
+            name: String::from("main"),
+            offset: 1,
+            parameters: Vec::new(),
+        };
+        let script = [PUSH1, RET];
+        assert_eq!(
+            method.entry(&script).unwrap(),
+            [JMP_L, 6, 0, 0, 0, PUSH1, RET]
+        );
+        let outside = Method {
+            offset: 2,
+            ..method
+        };
+        assert_eq!(outside.entry(&script), None);
+    }
+}
@@ -9,8 +9,12 @@ use libafl_bolts::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    input::ByteCodeInput,
+    config::Config,
+    input::ProgramInput,
+    limits::Limits,
+    oracle,
     output::{parse, Output},
+    reference,
 };
 
 #[derive(Clone)]
@@ -20,21 +24,32 @@ pub struct DiffStdOutObjective {
     diff_std_out_metadata: DiffStdOutMetadata,
     detect_status_diff: bool,
     detect_crash_diff: bool,
+    /// Check output of each VM against the spec alone.
+    spec_oracle: bool,
+    /// Compare ids of stack items, i.e. which of them are the same object.
+    compare_aliasing: bool,
+    /// Profile harnesses are given, `None` if they run with their defaults.
+    limits: Option<Limits>,
+    /// Scripts run in an engine, which charges gas for every instruction.
+    engine: bool,
 }
 
 impl DiffStdOutObjective {
     pub fn new(
         fst_stdout_observer: Handle<StdOutObserver>,
         snd_stdout_observer: Handle<StdOutObserver>,
-        detect_status_diff: bool,
-        detect_crash_diff: bool,
+        config: &Config,
     ) -> DiffStdOutObjective {
         DiffStdOutObjective {
-            fst_stdout_observer: fst_stdout_observer,
-            snd_stdout_observer: snd_stdout_observer,
+            fst_stdout_observer,
+            snd_stdout_observer,
             diff_std_out_metadata: DiffStdOutMetadata::default(),
-            detect_status_diff: detect_status_diff,
-            detect_crash_diff: detect_crash_diff,
+            detect_status_diff: config.detect_status_diff,
+            detect_crash_diff: config.detect_crash_diff,
+            spec_oracle: config.spec_oracle,
+            compare_aliasing: config.compare_aliasing,
+            limits: config.limits_profile(),
+            engine: !config.syscalls.is_empty(),
         }
     }
 }
@@ -45,13 +60,35 @@ struct DiffStdOutMetadata {
     fst: Option<Output>,
     snd: Option<Output>,
     cause: Option<String>,
+    /// Limits both VMs were given (`null` if they ran with their defaults), outputs have the ones they ran with.
+    limits: Option<Limits>,
+    /// Which VM agrees with the reference interpreter: `fst`, `snd`, `both` or `neither`.
+    reference: Option<String>,
+}
+
+/// Runs the input on the reference interpreter, `None` if it is not supported.
+fn reference_label(
+    input: &ProgramInput,
+    limits: &Limits,
+    fst: Option<&Output>,
+    snd: Option<&Output>,
+) -> Option<String> {
+    let outcome = reference::run(input, limits)?;
+    let matches = |output: Option<&Output>| output.is_some_and(|output| outcome.matches(output));
+    let label = match (matches(fst), matches(snd)) {
+        (true, true) => "both",
+        (true, false) => "fst",
+        (false, true) => "snd",
+        (false, false) => "neither",
+    };
+    Some(String::from(label))
 }
 
 impl_serdeany!(DiffStdOutMetadata);
 
 impl<S> Feedback<S> for DiffStdOutObjective
 where
-    S: State + UsesInput<Input = ByteCodeInput>,
+    S: State + UsesInput<Input = ProgramInput>,
 {
     fn is_interesting<EM, OT>(
         &mut self,
@@ -66,6 +103,9 @@ where
         OT: ObserversTuple<S>,
     {
         self.diff_std_out_metadata = DiffStdOutMetadata::default();
+        let given = input.limits.or(self.limits);
+        // Harnesses running with their defaults are expected to have Neo defaults.
+        let limits = given.unwrap_or_default();
         let fst_out = observers
             .get(&self.fst_stdout_observer)
             .unwrap()
@@ -83,57 +123,71 @@ where
         match exit_kind {
             ExitKind::Diff { primary, secondary }
                 if self.detect_crash_diff
-                    && (primary.to_owned() == DiffExitKind::Crash
-                        || secondary.to_owned() == DiffExitKind::Crash) =>
+                    && (*primary == DiffExitKind::Crash || *secondary == DiffExitKind::Crash) =>
             {
+                let (fst, snd) = (parse(&fst_out), parse(&snd_out));
                 self.diff_std_out_metadata = DiffStdOutMetadata {
                     base64: Some(input.as_standard_base64()),
-                    fst: parse(&fst_out),
-                    snd: parse(&snd_out),
+                    reference: reference_label(input, &limits, fst.as_ref(), snd.as_ref()),
+                    fst,
+                    snd,
                     cause: Some(
                         String::from("different exit code: ")
                             + &serde_json::to_string(primary).unwrap().replace("\"", "")
                             + " / "
                             + &serde_json::to_string(secondary).unwrap().replace("\"", ""),
                     ),
+                    limits: given,
                 };
                 return Ok(true);
             }
             _ => (),
         }
-        match (parse(&fst_out), parse(&snd_out)) {
-            (Some(fst_out), Some(snd_out)) if fst_out.status == snd_out.status => {
-                match fst_out.status.as_str() {
-                    "VM halted" => {
-                        if fst_out.estack != snd_out.estack {
-                            self.diff_std_out_metadata = DiffStdOutMetadata {
-                                base64: Some(input.as_standard_base64()),
-                                fst: Some(fst_out),
-                                snd: Some(snd_out),
-                                cause: Some(String::from("different stack")),
-                            };
-                            Ok(true)
-                        } else {
-                            Ok(false)
-                        }
-                    }
-                    _ => Ok(false),
-                }
-            }
-            (Some(fst_out), Some(snd_out))
-                if fst_out.status != snd_out.status && self.detect_status_diff =>
-            {
-                self.diff_std_out_metadata = DiffStdOutMetadata {
-                    base64: Some(input.as_standard_base64()),
-                    fst: Some(fst_out),
-                    snd: Some(snd_out),
-                    cause: Some(String::from("different status")),
-                };
-                Ok(true)
+        let (Some(fst_out), Some(snd_out)) = (parse(&fst_out), parse(&snd_out)) else {
+            return Ok(false);
+        };
+        let cause = if fst_out.status != snd_out.status {
+            self.detect_status_diff
+                .then(|| String::from("different status"))
+        } else if fst_out.status == "VM halted" {
+            fst_out
+                .diff_halted(&snd_out, self.compare_aliasing)
+                .map(String::from)
+                .or_else(|| {
+                    fst_out
+                        .violation()
+                        .map(|violation| format!("invariant violation: {violation}"))
+                })
+        } else {
+            None
+        };
+        let cause = cause.or_else(|| {
+            if !self.spec_oracle {
+                return None;
             }
-            (None, None) => Ok(false),
-            _ => Ok(false),
-        }
+            let (fst_name, snd_name) = (
+                self.fst_stdout_observer.name(),
+                self.snd_stdout_observer.name(),
+            );
+            oracle::check(&fst_out, input, &limits, self.engine)
+                .map(|violation| format!("spec violation ({fst_name}): {violation}"))
+                .or_else(|| {
+                    oracle::check(&snd_out, input, &limits, self.engine)
+                        .map(|violation| format!("spec violation ({snd_name}): {violation}"))
+                })
+        });
+        let Some(cause) = cause else {
+            return Ok(false);
+        };
+        self.diff_std_out_metadata = DiffStdOutMetadata {
+            base64: Some(input.as_standard_base64()),
+            reference: reference_label(input, &limits, Some(&fst_out), Some(&snd_out)),
+            fst: Some(fst_out),
+            snd: Some(snd_out),
+            cause: Some(cause),
+            limits: given,
+        };
+        Ok(true)
     }
 
     fn append_metadata<EM, OT>(
@@ -6,13 +6,80 @@ use std::{
     process::Command,
 };
 
-use libafl::{inputs::UsesInput, prelude::Observer};
-use libafl_bolts::Named;
+use libafl::{
+    inputs::UsesInput,
+    observers::{HitcountsMapObserver, StdMapObserver},
+    prelude::Observer,
+};
+use libafl_bolts::{hash_std, ownedref::OwnedMutSlice, Named};
 use serde::{Deserialize, Serialize};
 
+/// Which Go packages and files are taken into account when collecting coverage.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct GoCoverFilter {
+    /// Package patterns (`...` matches any string, as in `go` tool), all packages if empty.
+    pub packages: Vec<String>,
+    pub exclude_packages: Vec<String>,
+    /// File path patterns (`*` matches any string), all files if empty.
+    pub files: Vec<String>,
+    pub exclude_files: Vec<String>,
+}
+
+impl GoCoverFilter {
+    /// Check if block in given file (e.g. `github.com/nspcc-dev/neo-go/pkg/vm/vm.go`) should be counted.
+    pub fn matches(&self, file: &str) -> bool {
+        let package = file.rsplit_once('/').map_or("", |(package, _)| package);
+        let any = |patterns: &[String], s: &str| patterns.iter().any(|p| wildcard_match(p, s));
+        (self.packages.is_empty() || any(&self.packages, package))
+            && !any(&self.exclude_packages, package)
+            && (self.files.is_empty() || any(&self.files, file))
+            && !any(&self.exclude_files, file)
+    }
+}
+
+/// Match string against pattern where both `...` and `*` match any (possibly empty) string.
+/// As in `go` tool, `foo/...` also matches `foo`.
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix("/...") {
+        if s == prefix {
+            return true;
+        }
+    }
+    let pattern = pattern.replace("...", "*");
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return s == first;
+    }
+    if !s.starts_with(first) || !s[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &s[first.len()..s.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Size of Go coverage hit counts map, blocks are put into it by hash of their location.
+pub const GO_COVER_MAP_SIZE: usize = 65536;
+
+/// Name of the observer of bucketed hit counts (and of history of its feedback).
+pub const GO_HITCOUNT_OBSERVER: &str = "go-hitcount-observer";
+
+/// Hit counts written by [`GoCoverObserver`], put into AFL buckets.
+pub type GoHitcountObserver = HitcountsMapObserver<StdMapObserver<'static, u8, false>>;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GoCoverObserver {
     pub coverage: HashSet<String>,
+    /// Raw hit counts of blocks, shared with map observer that classifies them into buckets.
+    hitcounts: OwnedMutSlice<'static, u8>,
+    filter: GoCoverFilter,
     cover_dir: Box<Path>,
     cover_merged_dir: Box<Path>,
     cover_tmp_dir: Box<Path>,
@@ -21,6 +88,8 @@ pub struct GoCoverObserver {
 
 impl GoCoverObserver {
     pub fn new(
+        hitcounts: OwnedMutSlice<'static, u8>,
+        filter: GoCoverFilter,
         cover_dir: Box<Path>,
         cover_merged_dir: Box<Path>,
         cover_tmp_dir: Box<Path>,
@@ -29,6 +98,8 @@ impl GoCoverObserver {
         profile_path.push("profile.txt");
         GoCoverObserver {
             coverage: HashSet::new(),
+            hitcounts,
+            filter,
             cover_dir,
             cover_merged_dir,
             cover_tmp_dir,
@@ -72,9 +143,8 @@ where
             .arg("-i")
             .arg(self.cover_dir.as_ref())
             .arg("-o")
-            .arg(self.profile_path.as_ref())
-            .arg("-pkg")
-            .arg("github.com/nspcc-dev/neo-go/pkg/vm");
+            .arg(self.profile_path.as_ref());
+        // `-pkg` of covdata only takes exact import paths, so patterns are matched against the profile instead.
         cover_cmd.output()?;
         for line in read_to_string(self.profile_path.as_ref())?.lines() {
             if line.starts_with("mode") {
@@ -82,9 +152,13 @@ where
             }
             let data: Vec<_> = line.split(' ').collect();
             let location = data[0];
-            let count: i32 = data[2].parse().unwrap();
-            if count != 0 {
+            let count: u64 = data[2].parse().unwrap();
+            let file = location.split(':').next().unwrap_or(location);
+            if count != 0 && self.filter.matches(file) {
                 self.coverage.insert(String::from(location));
+                let idx = hash_std(location.as_bytes()) as usize % self.hitcounts.len();
+                let hits = &mut self.hitcounts[idx];
+                *hits = hits.saturating_add(count.min(u8::MAX as u64) as u8);
             }
         }
         for entry in std::fs::read_dir(self.cover_tmp_dir.as_ref())? {
@@ -141,3 +215,44 @@ impl Named for GoCoverObserver {
         &Cow::Borrowed("GoCoverObserver")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("foo", "foo"));
+        assert!(!wildcard_match("foo", "foobar"));
+        assert!(wildcard_match("foo/...", "foo"));
+        assert!(wildcard_match("foo/...", "foo/bar/baz"));
+        assert!(!wildcard_match("foo/...", "foobar"));
+        assert!(wildcard_match("*.go", "vm.go"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*b*c", "abc"));
+        assert!(wildcard_match("a*b*c", "a-b-b-c"));
+        assert!(!wildcard_match("a*b*c", "a-c"));
+        // Prefix and suffix must not overlap.
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(wildcard_match(
+            ".../vm/...",
+            "github.com/nspcc-dev/neo-go/pkg/vm/stackitem"
+        ));
+    }
+
+    #[test]
+    fn filter() {
+        let filter = GoCoverFilter {
+            packages: vec![String::from("github.com/nspcc-dev/neo-go/pkg/vm/...")],
+            exclude_packages: vec![String::from(".../vm/opcode")],
+            files: Vec::new(),
+            exclude_files: vec![String::from("*_string.go")],
+        };
+        assert!(filter.matches("github.com/nspcc-dev/neo-go/pkg/vm/vm.go"));
+        assert!(filter.matches("github.com/nspcc-dev/neo-go/pkg/vm/stackitem/item.go"));
+        assert!(!filter.matches("github.com/nspcc-dev/neo-go/pkg/vm/opcode/opcode.go"));
+        assert!(!filter.matches("github.com/nspcc-dev/neo-go/pkg/vm/stackitem/type_string.go"));
+        assert!(!filter.matches("github.com/nspcc-dev/neo-go/pkg/core/blockchain.go"));
+        assert!(GoCoverFilter::default().matches("any/file.go"));
+    }
+}
@@ -0,0 +1,485 @@
+use Operand::{Fixed, Prefix};
+
+/// Size of instruction operand in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// Operand of fixed size.
+    Fixed(usize),
+    /// Data prefixed with its length (size of prefix is given).
+    Prefix(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpCodeInfo {
+    pub code: u8,
+    pub name: &'static str,
+    pub operand: Operand,
+}
+
+pub const PUSHINT8: u8 = 0x00;
+pub const PUSHINT16: u8 = 0x01;
+pub const PUSHINT32: u8 = 0x02;
+pub const PUSHINT64: u8 = 0x03;
+pub const PUSHINT128: u8 = 0x04;
+pub const PUSHINT256: u8 = 0x05;
+pub const PUSHT: u8 = 0x08;
+pub const PUSHF: u8 = 0x09;
+pub const PUSHA: u8 = 0x0A;
+pub const PUSHNULL: u8 = 0x0B;
+pub const PUSHDATA1: u8 = 0x0C;
+pub const PUSHDATA2: u8 = 0x0D;
+pub const PUSHDATA4: u8 = 0x0E;
+pub const PUSHM1: u8 = 0x0F;
+pub const PUSH0: u8 = 0x10;
+pub const PUSH1: u8 = 0x11;
+pub const PUSH2: u8 = 0x12;
+pub const PUSH3: u8 = 0x13;
+pub const PUSH4: u8 = 0x14;
+pub const PUSH5: u8 = 0x15;
+pub const PUSH6: u8 = 0x16;
+pub const PUSH7: u8 = 0x17;
+pub const PUSH8: u8 = 0x18;
+pub const PUSH9: u8 = 0x19;
+pub const PUSH10: u8 = 0x1A;
+pub const PUSH11: u8 = 0x1B;
+pub const PUSH12: u8 = 0x1C;
+pub const PUSH13: u8 = 0x1D;
+pub const PUSH14: u8 = 0x1E;
+pub const PUSH15: u8 = 0x1F;
+pub const PUSH16: u8 = 0x20;
+pub const NOP: u8 = 0x21;
+pub const JMP: u8 = 0x22;
+pub const JMP_L: u8 = 0x23;
+pub const JMPIF: u8 = 0x24;
+pub const JMPIF_L: u8 = 0x25;
+pub const JMPIFNOT: u8 = 0x26;
+pub const JMPIFNOT_L: u8 = 0x27;
+pub const JMPEQ: u8 = 0x28;
+pub const JMPEQ_L: u8 = 0x29;
+pub const JMPNE: u8 = 0x2A;
+pub const JMPNE_L: u8 = 0x2B;
+pub const JMPGT: u8 = 0x2C;
+pub const JMPGT_L: u8 = 0x2D;
+pub const JMPGE: u8 = 0x2E;
+pub const JMPGE_L: u8 = 0x2F;
+pub const JMPLT: u8 = 0x30;
+pub const JMPLT_L: u8 = 0x31;
+pub const JMPLE: u8 = 0x32;
+pub const JMPLE_L: u8 = 0x33;
+pub const CALL: u8 = 0x34;
+pub const CALL_L: u8 = 0x35;
+pub const CALLA: u8 = 0x36;
+pub const CALLT: u8 = 0x37;
+pub const ABORT: u8 = 0x38;
+pub const ASSERT: u8 = 0x39;
+pub const THROW: u8 = 0x3A;
+pub const TRY: u8 = 0x3B;
+pub const TRY_L: u8 = 0x3C;
+pub const ENDTRY: u8 = 0x3D;
+pub const ENDTRY_L: u8 = 0x3E;
+pub const ENDFINALLY: u8 = 0x3F;
+pub const RET: u8 = 0x40;
+pub const SYSCALL: u8 = 0x41;
+pub const DEPTH: u8 = 0x43;
+pub const DROP: u8 = 0x45;
+pub const NIP: u8 = 0x46;
+pub const XDROP: u8 = 0x48;
+pub const CLEAR: u8 = 0x49;
+pub const DUP: u8 = 0x4A;
+pub const OVER: u8 = 0x4B;
+pub const PICK: u8 = 0x4D;
+pub const TUCK: u8 = 0x4E;
+pub const SWAP: u8 = 0x50;
+pub const ROT: u8 = 0x51;
+pub const ROLL: u8 = 0x52;
+pub const REVERSE3: u8 = 0x53;
+pub const REVERSE4: u8 = 0x54;
+pub const REVERSEN: u8 = 0x55;
+pub const INITSSLOT: u8 = 0x56;
+pub const INITSLOT: u8 = 0x57;
+pub const LDSFLD0: u8 = 0x58;
+pub const LDSFLD1: u8 = 0x59;
+pub const LDSFLD2: u8 = 0x5A;
+pub const LDSFLD3: u8 = 0x5B;
+pub const LDSFLD4: u8 = 0x5C;
+pub const LDSFLD5: u8 = 0x5D;
+pub const LDSFLD6: u8 = 0x5E;
+pub const LDSFLD: u8 = 0x5F;
+pub const STSFLD0: u8 = 0x60;
+pub const STSFLD1: u8 = 0x61;
+pub const STSFLD2: u8 = 0x62;
+pub const STSFLD3: u8 = 0x63;
+pub const STSFLD4: u8 = 0x64;
+pub const STSFLD5: u8 = 0x65;
+pub const STSFLD6: u8 = 0x66;
+pub const STSFLD: u8 = 0x67;
+pub const LDLOC0: u8 = 0x68;
+pub const LDLOC1: u8 = 0x69;
+pub const LDLOC2: u8 = 0x6A;
+pub const LDLOC3: u8 = 0x6B;
+pub const LDLOC4: u8 = 0x6C;
+pub const LDLOC5: u8 = 0x6D;
+pub const LDLOC6: u8 = 0x6E;
+pub const LDLOC: u8 = 0x6F;
+pub const STLOC0: u8 = 0x70;
+pub const STLOC1: u8 = 0x71;
+pub const STLOC2: u8 = 0x72;
+pub const STLOC3: u8 = 0x73;
+pub const STLOC4: u8 = 0x74;
+pub const STLOC5: u8 = 0x75;
+pub const STLOC6: u8 = 0x76;
+pub const STLOC: u8 = 0x77;
+pub const LDARG0: u8 = 0x78;
+pub const LDARG1: u8 = 0x79;
+pub const LDARG2: u8 = 0x7A;
+pub const LDARG3: u8 = 0x7B;
+pub const LDARG4: u8 = 0x7C;
+pub const LDARG5: u8 = 0x7D;
+pub const LDARG6: u8 = 0x7E;
+pub const LDARG: u8 = 0x7F;
+pub const STARG0: u8 = 0x80;
+pub const STARG1: u8 = 0x81;
+pub const STARG2: u8 = 0x82;
+pub const STARG3: u8 = 0x83;
+pub const STARG4: u8 = 0x84;
+pub const STARG5: u8 = 0x85;
+pub const STARG6: u8 = 0x86;
+pub const STARG: u8 = 0x87;
+pub const NEWBUFFER: u8 = 0x88;
+pub const MEMCPY: u8 = 0x89;
+pub const CAT: u8 = 0x8B;
+pub const SUBSTR: u8 = 0x8C;
+pub const LEFT: u8 = 0x8D;
+pub const RIGHT: u8 = 0x8E;
+pub const INVERT: u8 = 0x90;
+pub const AND: u8 = 0x91;
+pub const OR: u8 = 0x92;
+pub const XOR: u8 = 0x93;
+pub const EQUAL: u8 = 0x97;
+pub const NOTEQUAL: u8 = 0x98;
+pub const SIGN: u8 = 0x99;
+pub const ABS: u8 = 0x9A;
+pub const NEGATE: u8 = 0x9B;
+pub const INC: u8 = 0x9C;
+pub const DEC: u8 = 0x9D;
+pub const ADD: u8 = 0x9E;
+pub const SUB: u8 = 0x9F;
+pub const MUL: u8 = 0xA0;
+pub const DIV: u8 = 0xA1;
+pub const MOD: u8 = 0xA2;
+pub const POW: u8 = 0xA3;
+pub const SQRT: u8 = 0xA4;
+pub const MODMUL: u8 = 0xA5;
+pub const MODPOW: u8 = 0xA6;
+pub const SHL: u8 = 0xA8;
+pub const SHR: u8 = 0xA9;
+pub const NOT: u8 = 0xAA;
+pub const BOOLAND: u8 = 0xAB;
+pub const BOOLOR: u8 = 0xAC;
+pub const NZ: u8 = 0xB1;
+pub const NUMEQUAL: u8 = 0xB3;
+pub const NUMNOTEQUAL: u8 = 0xB4;
+pub const LT: u8 = 0xB5;
+pub const LE: u8 = 0xB6;
+pub const GT: u8 = 0xB7;
+pub const GE: u8 = 0xB8;
+pub const MIN: u8 = 0xB9;
+pub const MAX: u8 = 0xBA;
+pub const WITHIN: u8 = 0xBB;
+pub const PACKMAP: u8 = 0xBE;
+pub const PACKSTRUCT: u8 = 0xBF;
+pub const PACK: u8 = 0xC0;
+pub const UNPACK: u8 = 0xC1;
+pub const NEWARRAY0: u8 = 0xC2;
+pub const NEWARRAY: u8 = 0xC3;
+pub const NEWARRAY_T: u8 = 0xC4;
+pub const NEWSTRUCT0: u8 = 0xC5;
+pub const NEWSTRUCT: u8 = 0xC6;
+pub const NEWMAP: u8 = 0xC8;
+pub const SIZE: u8 = 0xCA;
+pub const HASKEY: u8 = 0xCB;
+pub const KEYS: u8 = 0xCC;
+pub const VALUES: u8 = 0xCD;
+pub const PICKITEM: u8 = 0xCE;
+pub const APPEND: u8 = 0xCF;
+pub const SETITEM: u8 = 0xD0;
+pub const REVERSEITEMS: u8 = 0xD1;
+pub const REMOVE: u8 = 0xD2;
+pub const CLEARITEMS: u8 = 0xD3;
+pub const POPITEM: u8 = 0xD4;
+pub const ISNULL: u8 = 0xD8;
+pub const ISTYPE: u8 = 0xD9;
+pub const CONVERT: u8 = 0xDB;
+pub const ABORTMSG: u8 = 0xE0;
+pub const ASSERTMSG: u8 = 0xE1;
+
+const fn info(code: u8, name: &'static str, operand: Operand) -> OpCodeInfo {
+    OpCodeInfo {
+        code,
+        name,
+        operand,
+    }
+}
+
+pub const OPCODES: &[OpCodeInfo] = &[
+    info(PUSHINT8, "PUSHINT8", Fixed(1)),
+    info(PUSHINT16, "PUSHINT16", Fixed(2)),
+    info(PUSHINT32, "PUSHINT32", Fixed(4)),
+    info(PUSHINT64, "PUSHINT64", Fixed(8)),
+    info(PUSHINT128, "PUSHINT128", Fixed(16)),
+    info(PUSHINT256, "PUSHINT256", Fixed(32)),
+    info(PUSHT, "PUSHT", Operand::None),
+    info(PUSHF, "PUSHF", Operand::None),
+    info(PUSHA, "PUSHA", Fixed(4)),
+    info(PUSHNULL, "PUSHNULL", Operand::None),
+    info(PUSHDATA1, "PUSHDATA1", Prefix(1)),
+    info(PUSHDATA2, "PUSHDATA2", Prefix(2)),
+    info(PUSHDATA4, "PUSHDATA4", Prefix(4)),
+    info(PUSHM1, "PUSHM1", Operand::None),
+    info(PUSH0, "PUSH0", Operand::None),
+    info(PUSH1, "PUSH1", Operand::None),
+    info(PUSH2, "PUSH2", Operand::None),
+    info(PUSH3, "PUSH3", Operand::None),
+    info(PUSH4, "PUSH4", Operand::None),
+    info(PUSH5, "PUSH5", Operand::None),
+    info(PUSH6, "PUSH6", Operand::None),
+    info(PUSH7, "PUSH7", Operand::None),
+    info(PUSH8, "PUSH8", Operand::None),
+    info(PUSH9, "PUSH9", Operand::None),
+    info(PUSH10, "PUSH10", Operand::None),
+    info(PUSH11, "PUSH11", Operand::None),
+    info(PUSH12, "PUSH12", Operand::None),
+    info(PUSH13, "PUSH13", Operand::None),
+    info(PUSH14, "PUSH14", Operand::None),
+    info(PUSH15, "PUSH15", Operand::None),
+    info(PUSH16, "PUSH16", Operand::None),
+    info(NOP, "NOP", Operand::None),
+    info(JMP, "JMP", Fixed(1)),
+    info(JMP_L, "JMP_L", Fixed(4)),
+    info(JMPIF, "JMPIF", Fixed(1)),
+    info(JMPIF_L, "JMPIF_L", Fixed(4)),
+    info(JMPIFNOT, "JMPIFNOT", Fixed(1)),
+    info(JMPIFNOT_L, "JMPIFNOT_L", Fixed(4)),
+    info(JMPEQ, "JMPEQ", Fixed(1)),
+    info(JMPEQ_L, "JMPEQ_L", Fixed(4)),
+    info(JMPNE, "JMPNE", Fixed(1)),
+    info(JMPNE_L, "JMPNE_L", Fixed(4)),
+    info(JMPGT, "JMPGT", Fixed(1)),
+    info(JMPGT_L, "JMPGT_L", Fixed(4)),
+    info(JMPGE, "JMPGE", Fixed(1)),
+    info(JMPGE_L, "JMPGE_L", Fixed(4)),
+    info(JMPLT, "JMPLT", Fixed(1)),
+    info(JMPLT_L, "JMPLT_L", Fixed(4)),
+    info(JMPLE, "JMPLE", Fixed(1)),
+    info(JMPLE_L, "JMPLE_L", Fixed(4)),
+    info(CALL, "CALL", Fixed(1)),
+    info(CALL_L, "CALL_L", Fixed(4)),
+    info(CALLA, "CALLA", Operand::None),
+    info(CALLT, "CALLT", Fixed(2)),
+    info(ABORT, "ABORT", Operand::None),
+    info(ASSERT, "ASSERT", Operand::None),
+    info(THROW, "THROW", Operand::None),
+    info(TRY, "TRY", Fixed(2)),
+    info(TRY_L, "TRY_L", Fixed(8)),
+    info(ENDTRY, "ENDTRY", Fixed(1)),
+    info(ENDTRY_L, "ENDTRY_L", Fixed(4)),
+    info(ENDFINALLY, "ENDFINALLY", Operand::None),
+    info(RET, "RET", Operand::None),
+    info(SYSCALL, "SYSCALL", Fixed(4)),
+    info(DEPTH, "DEPTH", Operand::None),
+    info(DROP, "DROP", Operand::None),
+    info(NIP, "NIP", Operand::None),
+    info(XDROP, "XDROP", Operand::None),
+    info(CLEAR, "CLEAR", Operand::None),
+    info(DUP, "DUP", Operand::None),
+    info(OVER, "OVER", Operand::None),
+    info(PICK, "PICK", Operand::None),
+    info(TUCK, "TUCK", Operand::None),
+    info(SWAP, "SWAP", Operand::None),
+    info(ROT, "ROT", Operand::None),
+    info(ROLL, "ROLL", Operand::None),
+    info(REVERSE3, "REVERSE3", Operand::None),
+    info(REVERSE4, "REVERSE4", Operand::None),
+    info(REVERSEN, "REVERSEN", Operand::None),
+    info(INITSSLOT, "INITSSLOT", Fixed(1)),
+    info(INITSLOT, "INITSLOT", Fixed(2)),
+    info(LDSFLD0, "LDSFLD0", Operand::None),
+    info(LDSFLD1, "LDSFLD1", Operand::None),
+    info(LDSFLD2, "LDSFLD2", Operand::None),
+    info(LDSFLD3, "LDSFLD3", Operand::None),
+    info(LDSFLD4, "LDSFLD4", Operand::None),
+    info(LDSFLD5, "LDSFLD5", Operand::None),
+    info(LDSFLD6, "LDSFLD6", Operand::None),
+    info(LDSFLD, "LDSFLD", Fixed(1)),
+    info(STSFLD0, "STSFLD0", Operand::None),
+    info(STSFLD1, "STSFLD1", Operand::None),
+    info(STSFLD2, "STSFLD2", Operand::None),
+    info(STSFLD3, "STSFLD3", Operand::None),
+    info(STSFLD4, "STSFLD4", Operand::None),
+    info(STSFLD5, "STSFLD5", Operand::None),
+    info(STSFLD6, "STSFLD6", Operand::None),
+    info(STSFLD, "STSFLD", Fixed(1)),
+    info(LDLOC0, "LDLOC0", Operand::None),
+    info(LDLOC1, "LDLOC1", Operand::None),
+    info(LDLOC2, "LDLOC2", Operand::None),
+    info(LDLOC3, "LDLOC3", Operand::None),
+    info(LDLOC4, "LDLOC4", Operand::None),
+    info(LDLOC5, "LDLOC5", Operand::None),
+    info(LDLOC6, "LDLOC6", Operand::None),
+    info(LDLOC, "LDLOC", Fixed(1)),
+    info(STLOC0, "STLOC0", Operand::None),
+    info(STLOC1, "STLOC1", Operand::None),
+    info(STLOC2, "STLOC2", Operand::None),
+    info(STLOC3, "STLOC3", Operand::None),
+    info(STLOC4, "STLOC4", Operand::None),
+    info(STLOC5, "STLOC5", Operand::None),
+    info(STLOC6, "STLOC6", Operand::None),
+    info(STLOC, "STLOC", Fixed(1)),
+    info(LDARG0, "LDARG0", Operand::None),
+    info(LDARG1, "LDARG1", Operand::None),
+    info(LDARG2, "LDARG2", Operand::None),
+    info(LDARG3, "LDARG3", Operand::None),
+    info(LDARG4, "LDARG4", Operand::None),
+    info(LDARG5, "LDARG5", Operand::None),
+    info(LDARG6, "LDARG6", Operand::None),
+    info(LDARG, "LDARG", Fixed(1)),
+    info(STARG0, "STARG0", Operand::None),
+    info(STARG1, "STARG1", Operand::None),
+    info(STARG2, "STARG2", Operand::None),
+    info(STARG3, "STARG3", Operand::None),
+    info(STARG4, "STARG4", Operand::None),
+    info(STARG5, "STARG5", Operand::None),
+    info(STARG6, "STARG6", Operand::None),
+    info(STARG, "STARG", Fixed(1)),
+    info(NEWBUFFER, "NEWBUFFER", Operand::None),
+    info(MEMCPY, "MEMCPY", Operand::None),
+    info(CAT, "CAT", Operand::None),
+    info(SUBSTR, "SUBSTR", Operand::None),
+    info(LEFT, "LEFT", Operand::None),
+    info(RIGHT, "RIGHT", Operand::None),
+    info(INVERT, "INVERT", Operand::None),
+    info(AND, "AND", Operand::None),
+    info(OR, "OR", Operand::None),
+    info(XOR, "XOR", Operand::None),
+    info(EQUAL, "EQUAL", Operand::None),
+    info(NOTEQUAL, "NOTEQUAL", Operand::None),
+    info(SIGN, "SIGN", Operand::None),
+    info(ABS, "ABS", Operand::None),
+    info(NEGATE, "NEGATE", Operand::None),
+    info(INC, "INC", Operand::None),
+    info(DEC, "DEC", Operand::None),
+    info(ADD, "ADD", Operand::None),
+    info(SUB, "SUB", Operand::None),
+    info(MUL, "MUL", Operand::None),
+    info(DIV, "DIV", Operand::None),
+    info(MOD, "MOD", Operand::None),
+    info(POW, "POW", Operand::None),
+    info(SQRT, "SQRT", Operand::None),
+    info(MODMUL, "MODMUL", Operand::None),
+    info(MODPOW, "MODPOW", Operand::None),
+    info(SHL, "SHL", Operand::None),
+    info(SHR, "SHR", Operand::None),
+    info(NOT, "NOT", Operand::None),
+    info(BOOLAND, "BOOLAND", Operand::None),
+    info(BOOLOR, "BOOLOR", Operand::None),
+    info(NZ, "NZ", Operand::None),
+    info(NUMEQUAL, "NUMEQUAL", Operand::None),
+    info(NUMNOTEQUAL, "NUMNOTEQUAL", Operand::None),
+    info(LT, "LT", Operand::None),
+    info(LE, "LE", Operand::None),
+    info(GT, "GT", Operand::None),
+    info(GE, "GE", Operand::None),
+    info(MIN, "MIN", Operand::None),
+    info(MAX, "MAX", Operand::None),
+    info(WITHIN, "WITHIN", Operand::None),
+    info(PACKMAP, "PACKMAP", Operand::None),
+    info(PACKSTRUCT, "PACKSTRUCT", Operand::None),
+    info(PACK, "PACK", Operand::None),
+    info(UNPACK, "UNPACK", Operand::None),
+    info(NEWARRAY0, "NEWARRAY0", Operand::None),
+    info(NEWARRAY, "NEWARRAY", Operand::None),
+    info(NEWARRAY_T, "NEWARRAY_T", Fixed(1)),
+    info(NEWSTRUCT0, "NEWSTRUCT0", Operand::None),
+    info(NEWSTRUCT, "NEWSTRUCT", Operand::None),
+    info(NEWMAP, "NEWMAP", Operand::None),
+    info(SIZE, "SIZE", Operand::None),
+    info(HASKEY, "HASKEY", Operand::None),
+    info(KEYS, "KEYS", Operand::None),
+    info(VALUES, "VALUES", Operand::None),
+    info(PICKITEM, "PICKITEM", Operand::None),
+    info(APPEND, "APPEND", Operand::None),
+    info(SETITEM, "SETITEM", Operand::None),
+    info(REVERSEITEMS, "REVERSEITEMS", Operand::None),
+    info(REMOVE, "REMOVE", Operand::None),
+    info(CLEARITEMS, "CLEARITEMS", Operand::None),
+    info(POPITEM, "POPITEM", Operand::None),
+    info(ISNULL, "ISNULL", Operand::None),
+    info(ISTYPE, "ISTYPE", Fixed(1)),
+    info(CONVERT, "CONVERT", Fixed(1)),
+    info(ABORTMSG, "ABORTMSG", Operand::None),
+    info(ASSERTMSG, "ASSERTMSG", Operand::None),
+];
+
+/// Find opcode by its name (case insensitive).
+pub fn by_name(name: &str) -> Option<&'static OpCodeInfo> {
+    OPCODES
+        .iter()
+        .find(|info| info.name.eq_ignore_ascii_case(name))
+}
+
+/// Find opcode by its code.
+pub fn by_code(code: u8) -> Option<&'static OpCodeInfo> {
+    OPCODES.iter().find(|info| info.code == code)
+}
+
+/// Decoded instruction of a script.
+#[derive(Debug, Clone, Copy)]
+pub struct Instruction<'a> {
+    pub offset: usize,
+    /// Raw operand, including length prefix.
+    pub operand: &'a [u8],
+}
+
+impl Instruction<'_> {
+    /// Size of instruction in bytes.
+    pub fn size(&self) -> usize {
+        1 + self.operand.len()
+    }
+}
+
+/// Iterator over instructions of a script, stops at unknown opcode or truncated operand.
+pub struct Instructions<'a> {
+    script: &'a [u8],
+    offset: usize,
+}
+
+pub fn instructions(script: &[u8]) -> Instructions<'_> {
+    Instructions { script, offset: 0 }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Instruction<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = by_code(*self.script.get(self.offset)?)?;
+        let start = self.offset + 1;
+        let size = match opcode.operand {
+            Operand::None => 0,
+            Fixed(size) => size,
+            Prefix(prefix) => {
+                let len = self.script.get(start..start + prefix)?;
+                let mut buf = [0u8; 8];
+                buf[..prefix].copy_from_slice(len);
+                prefix.checked_add(usize::try_from(u64::from_le_bytes(buf)).ok()?)?
+            }
+        };
+        let operand = self.script.get(start..start.checked_add(size)?)?;
+        let instruction = Instruction {
+            offset: self.offset,
+            operand,
+        };
+        self.offset += instruction.size();
+        Some(instruction)
+    }
+}
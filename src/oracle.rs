@@ -0,0 +1,190 @@
+use base64::prelude::*;
+
+use crate::{
+    input::ProgramInput,
+    item,
+    limits::Limits,
+    opcode::{self, ABORT, ABORTMSG, RET, THROW},
+    output::{Output, StackItem},
+};
+
+/// First property of NeoVM spec that output of a single VM violates.
+/// Scripts may halt by running off their end, so halted ones are only checked not to stop at
+/// `ABORT`, `ABORTMSG` or `THROW`, and faulted ones not to stop at the final `RET` of a script
+/// running alone in the bare VM (callees and gas are not taken into account).
+pub fn check(
+    output: &Output,
+    input: &ProgramInput,
+    limits: &Limits,
+    engine: bool,
+) -> Option<String> {
+    if let Some(violation) = check_lastop(output, input, engine) {
+        return Some(violation);
+    }
+    output
+        .estack
+        .iter()
+        .chain(
+            output
+                .notifications
+                .iter()
+                .map(|notification| &notification.state),
+        )
+        .find_map(|item| check_item(item, limits))
+}
+
+/// Last opcode consistent with the status.
+fn check_lastop(output: &Output, input: &ProgramInput, engine: bool) -> Option<String> {
+    let name = opcode::by_code(output.lastop).map_or("?", |info| info.name);
+    if output.status == "VM halted" {
+        return matches!(output.lastop, ABORT | ABORTMSG | THROW)
+            .then(|| format!("halted after {name}"));
+    }
+    let script = &input.entry.opcodes;
+    let ends_with_ret = opcode::instructions(script)
+        .last()
+        .is_some_and(|instr| script[instr.offset] == RET);
+    (output.lastop == RET && ends_with_ret && input.callees.is_empty() && !engine)
+        .then(|| format!("faulted at {name} at the end of the script"))
+}
+
+fn check_item(item: &StackItem, limits: &Limits) -> Option<String> {
+    let value = &item.ivalue;
+    match item.itype.as_str() {
+        "Boolean" if !value.is_boolean() => Some(format!("non-canonical boolean {value}")),
+        "Integer" => match value.as_str() {
+            Some(decimal) if item::from_decimal(decimal).is_some() => None,
+            _ => Some(format!("integer {value} out of 256-bit range")),
+        },
+        "ByteString" | "Buffer" => {
+            let Some(bytes) = value
+                .as_str()
+                .and_then(|value| BASE64_STANDARD.decode(value).ok())
+            else {
+                return Some(format!("invalid {} value {value}", item.itype));
+            };
+            (bytes.len() as u64 > limits.max_item_size).then(|| {
+                format!(
+                    "{} of {} bytes, limit is {}",
+                    item.itype,
+                    bytes.len(),
+                    limits.max_item_size
+                )
+            })
+        }
+        _ => {
+            if let Some(items) = item.items() {
+                return items.iter().find_map(|item| check_item(item, limits));
+            }
+            item.entries()?.iter().find_map(|entry| {
+                check_item(&entry.key, limits).or_else(|| check_item(&entry.value, limits))
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::ByteCodeInput,
+        opcode::{PUSH1, PUSHDATA1},
+        output::parse,
+    };
+
+    fn output(status: &str, lastop: u8, estack: &str) -> Output {
+        parse(
+            format!(
+                r#"{{"status": "{status}", "errmsg": "", "lastop": {lastop}, "estack": {estack}}}"#
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn script(opcodes: &[u8]) -> ProgramInput {
+        ProgramInput::from(ByteCodeInput {
+            opcodes: opcodes.to_vec(),
+        })
+    }
+
+    fn check_stack(estack: &str) -> Option<String> {
+        // Script without RET halts after its last instruction.
+        let output = output("VM halted", PUSH1, estack);
+        check(&output, &script(&[PUSH1]), &Limits::default(), false)
+    }
+
+    fn check_lastop(
+        status: &str,
+        lastop: u8,
+        input: &ProgramInput,
+        engine: bool,
+    ) -> Option<String> {
+        check(
+            &output(status, lastop, "[]"),
+            input,
+            &Limits::default(),
+            engine,
+        )
+    }
+
+    #[test]
+    fn lastop() {
+        let throw = script(&[PUSH1, THROW]);
+        assert_eq!(
+            check_lastop("VM halted", THROW, &throw, false),
+            Some(String::from("halted after THROW"))
+        );
+        assert_eq!(
+            check_lastop("VM halted", ABORTMSG, &throw, true),
+            Some(String::from("halted after ABORTMSG"))
+        );
+        assert_eq!(check_lastop("VM faulted", THROW, &throw, false), None);
+        assert_eq!(
+            check_lastop("VM halted", PUSH1, &script(&[PUSH1]), false),
+            None
+        );
+        let ret = script(&[PUSH1, RET]);
+        assert_eq!(
+            check_lastop("VM faulted", RET, &ret, false),
+            Some(String::from("faulted at RET at the end of the script"))
+        );
+        assert_eq!(check_lastop("VM halted", RET, &ret, false), None);
+        // Engine can run out of gas at RET, entry script returns into callees.
+        assert_eq!(check_lastop("VM faulted", RET, &ret, true), None);
+        let program = ProgramInput {
+            callees: vec![ByteCodeInput {
+                opcodes: vec![ABORT],
+            }],
+            ..ret.clone()
+        };
+        assert_eq!(check_lastop("VM faulted", RET, &program, false), None);
+        // RET is only the operand of the last instruction.
+        let operand = script(&[PUSHDATA1, 1, RET]);
+        assert_eq!(check_lastop("VM faulted", RET, &operand, false), None);
+    }
+
+    #[test]
+    fn items() {
+        assert_eq!(check_stack("[]"), None);
+        assert_eq!(
+            check_stack(r#"[{"type": "Boolean", "value": 1}]"#),
+            Some(String::from("non-canonical boolean 1"))
+        );
+        let max = format!("{}", (1u128 << 127) - 1);
+        assert_eq!(
+            check_stack(&format!(r#"[{{"type": "Integer", "value": "{max}"}}]"#)),
+            None
+        );
+        let too_big = format!("1{}", "0".repeat(78));
+        assert!(check_stack(&format!(
+            r#"[{{"type": "Array", "value": [{{"type": "Integer", "value": "{too_big}"}}]}}]"#
+        ))
+        .is_some_and(|violation| violation.contains("out of 256-bit range")));
+        let long = BASE64_STANDARD.encode(vec![0; Limits::default().max_item_size as usize + 1]);
+        assert!(
+            check_stack(&format!(r#"[{{"type": "Buffer", "value": "{long}"}}]"#))
+                .is_some_and(|violation| violation.starts_with("Buffer of 131071 bytes"))
+        );
+    }
+}
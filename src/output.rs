@@ -1,5 +1,10 @@
+use std::collections::HashMap;
+
+use base64::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+
+use crate::{item::MAX_KEY_SIZE, limits::Limits};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Output {
@@ -7,6 +12,18 @@ pub struct Output {
     pub errmsg: String,
     pub lastop: u8,
     pub estack: Vec<StackItem>,
+    /// Storage changes made in engine mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub storage: Vec<StorageItem>,
+    /// Events emitted by `System.Runtime.Notify` in engine mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<Notification>,
+    /// Messages of `System.Runtime.Log` in engine mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<Log>,
+    /// Limits the VM ran with, harnesses echo the ones they were given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<Limits>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -15,8 +32,353 @@ pub struct StackItem {
     pub itype: String,
     #[serde(rename = "value")]
     pub ivalue: Value,
+    /// Identity of `Array`, `Struct`, `Map` and `Buffer` items, the same object has the same id.
+    /// Ids are renumbered in the order of appearance when output is parsed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+}
+
+/// Element of printed `Map` value.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct MapEntry {
+    pub key: StackItem,
+    pub value: StackItem,
+}
+
+impl StackItem {
+    /// Elements of `Array` or `Struct`.
+    pub fn items(&self) -> Option<Vec<StackItem>> {
+        match self.itype.as_str() {
+            "Array" | "Struct" => serde_json::from_value(self.ivalue.clone()).ok(),
+            _ => None,
+        }
+    }
+
+    /// Elements of `Map`.
+    pub fn entries(&self) -> Option<Vec<MapEntry>> {
+        match self.itype.as_str() {
+            "Map" => serde_json::from_value(self.ivalue.clone()).ok(),
+            _ => None,
+        }
+    }
+
+    /// Replaces ids with the numbers of objects in the order they are met, `ids` maps ids printed by harness.
+    fn renumber_ids(&mut self, ids: &mut HashMap<u64, u64>) {
+        if let Some(id) = &mut self.id {
+            let next = ids.len() as u64;
+            *id = *ids.entry(*id).or_insert(next);
+        }
+        if let Some(mut items) = self.items() {
+            for item in &mut items {
+                item.renumber_ids(ids);
+            }
+            self.ivalue = json!(items);
+        } else if let Some(mut entries) = self.entries() {
+            for entry in &mut entries {
+                entry.key.renumber_ids(ids);
+                entry.value.renumber_ids(ids);
+            }
+            self.ivalue = json!(entries);
+        }
+    }
+
+    /// Copy without ids, map entries are sorted by key if `sort_maps` is set.
+    pub fn normalized(&self, sort_maps: bool) -> StackItem {
+        let mut item = StackItem {
+            id: None,
+            ..self.clone()
+        };
+        if let Some(items) = self.items() {
+            let items: Vec<_> = items
+                .iter()
+                .map(|item| item.normalized(sort_maps))
+                .collect();
+            item.ivalue = json!(items);
+        } else if let Some(entries) = self.entries() {
+            let mut entries: Vec<_> = entries
+                .iter()
+                .map(|entry| MapEntry {
+                    key: entry.key.normalized(sort_maps),
+                    value: entry.value.normalized(sort_maps),
+                })
+                .collect();
+            if sort_maps {
+                entries.sort_by_cached_key(|entry| serde_json::to_string(&entry.key).unwrap());
+            }
+            item.ivalue = json!(entries);
+        }
+        item
+    }
+
+    /// Map invariants: keys are unique primitive items of limited size.
+    fn violation(&self) -> Option<String> {
+        if let Some(items) = self.items() {
+            return items.iter().find_map(StackItem::violation);
+        }
+        let entries = self.entries()?;
+        for (i, entry) in entries.iter().enumerate() {
+            let key = &entry.key;
+            match key.itype.as_str() {
+                "Boolean" | "Integer" => (),
+                "ByteString" => {
+                    let size = key
+                        .ivalue
+                        .as_str()
+                        .and_then(|value| BASE64_STANDARD.decode(value).ok())
+                        .map_or(0, |value| value.len());
+                    if size > MAX_KEY_SIZE {
+                        return Some(format!("map key of {size} bytes"));
+                    }
+                }
+                itype => return Some(format!("map key of type {itype}")),
+            }
+            if entries[..i].iter().any(|other| other.key == *key) {
+                return Some(format!("duplicate map key {}", key.ivalue));
+            }
+        }
+        entries.iter().find_map(|entry| entry.value.violation())
+    }
+}
+
+/// Changed key and its new value in standard base64, `None` if the key was deleted.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+pub struct StorageItem {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+/// Contract hashes are in the form harnesses print them, e.g. `0x...`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Notification {
+    pub contract: String,
+    #[serde(rename = "eventname")]
+    pub name: String,
+    pub state: StackItem,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Log {
+    pub contract: String,
+    pub message: String,
+}
+
+impl Output {
+    /// Storage changes sorted by key, so that the order harnesses print them in doesn't matter.
+    pub fn sorted_storage(&self) -> Vec<StorageItem> {
+        let mut storage = self.storage.clone();
+        storage.sort();
+        storage
+    }
+
+    /// Invariant of NeoVM spec violated by the resulting stack of halted script.
+    pub fn violation(&self) -> Option<String> {
+        self.estack.iter().find_map(StackItem::violation)
+    }
+
+    /// Stack items without ids, map entries are sorted by key if `sort_maps` is set.
+    pub fn normalized_estack(&self, sort_maps: bool) -> Vec<StackItem> {
+        self.estack
+            .iter()
+            .map(|item| item.normalized(sort_maps))
+            .collect()
+    }
+
+    /// Numbers ids of stack items from the top one, then ids of notification states.
+    fn renumber_ids(&mut self) {
+        let mut ids = HashMap::new();
+        let states = self
+            .notifications
+            .iter_mut()
+            .map(|notification| &mut notification.state);
+        for item in self.estack.iter_mut().chain(states) {
+            item.renumber_ids(&mut ids);
+        }
+    }
+
+    /// Copy with ids removed from stack items and notification states.
+    fn without_ids(&self) -> Output {
+        let mut output = Output {
+            estack: self.normalized_estack(false),
+            ..self.clone()
+        };
+        for notification in &mut output.notifications {
+            notification.state = notification.state.normalized(false);
+        }
+        output
+    }
+
+    /// Cause of difference in effects of halted scripts, ids are only compared if `compare_ids` is set.
+    /// Stacks that are only different in order of map entries or in ids have their own causes,
+    /// maps keep insertion order and structs are copied by value unlike arrays, maps and buffers.
+    pub fn diff_halted(&self, other: &Output, compare_ids: bool) -> Option<&'static str> {
+        if !compare_ids {
+            return self.without_ids().diff_halted(&other.without_ids(), true);
+        }
+        if self.estack != other.estack {
+            if self.normalized_estack(false) == other.normalized_estack(false) {
+                Some("different aliasing")
+            } else if self.normalized_estack(true) == other.normalized_estack(true) {
+                Some("different map order")
+            } else {
+                Some("different stack")
+            }
+        } else if self.notifications != other.notifications {
+            Some("different notifications")
+        } else if self.logs != other.logs {
+            Some("different logs")
+        } else if self.sorted_storage() != other.sorted_storage() {
+            Some("different storage")
+        } else {
+            None
+        }
+    }
 }
 
-pub fn parse(data: &Vec<u8>) -> Option<Output> {
-    serde_json::from_slice(&data).ok()
+pub fn parse(data: &[u8]) -> Option<Output> {
+    let mut output: Output = serde_json::from_slice(data).ok()?;
+    output.renumber_ids();
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(json: &str) -> Output {
+        parse(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn diff_halted_causes() {
+        let base = output(r#"{"status": "VM halted", "errmsg": "", "lastop": 64, "estack": []}"#);
+        assert_eq!(base.diff_halted(&base, true), None);
+        let log = output(
+            r#"{"status": "VM halted", "errmsg": "", "lastop": 64, "estack": [],
+                "logs": [{"contract": "0x00", "message": "hi"}]}"#,
+        );
+        assert_eq!(base.diff_halted(&log, true), Some("different logs"));
+        let notification = output(
+            r#"{"status": "VM halted", "errmsg": "", "lastop": 64, "estack": [],
+                "logs": [{"contract": "0x00", "message": "hi"}],
+                "notifications": [{"contract": "0x00", "eventname": "e", "state": {"type": "Array", "value": []}}]}"#,
+        );
+        assert_eq!(
+            log.diff_halted(&notification, true),
+            Some("different notifications")
+        );
+        let storage = output(
+            r#"{"status": "VM halted", "errmsg": "", "lastop": 64, "estack": [],
+                "storage": [{"key": "AQ==", "value": null}, {"key": "AA==", "value": "AQ=="}]}"#,
+        );
+        assert_eq!(base.diff_halted(&storage, true), Some("different storage"));
+        let reordered = output(
+            r#"{"status": "VM halted", "errmsg": "", "lastop": 64, "estack": [],
+                "storage": [{"key": "AA==", "value": "AQ=="}, {"key": "AQ==", "value": null}]}"#,
+        );
+        assert_eq!(storage.diff_halted(&reordered, true), None);
+        let stack = output(
+            r#"{"status": "VM halted", "errmsg": "", "lastop": 64,
+                "estack": [{"type": "Integer", "value": "1"}]}"#,
+        );
+        assert_eq!(storage.diff_halted(&stack, true), Some("different stack"));
+    }
+
+    fn halted(estack: &str) -> Output {
+        output(&format!(
+            r#"{{"status": "VM halted", "errmsg": "", "lastop": 64, "estack": {estack}}}"#
+        ))
+    }
+
+    #[test]
+    fn ids_are_renumbered() {
+        let out = output(
+            r#"{"status": "VM halted", "errmsg": "", "lastop": 64,
+                "estack": [{"type": "Array", "id": 17, "value": [{"type": "Struct", "id": 5, "value": []}]},
+                           {"type": "Struct", "id": 5, "value": []}],
+                "notifications": [{"contract": "0x00", "eventname": "e",
+                                   "state": {"type": "Array", "id": 9, "value": [{"type": "Array", "id": 17, "value": []}]}}]}"#,
+        );
+        let nested = |item: &StackItem| item.items().unwrap()[0].id;
+        assert_eq!(out.estack[0].id, Some(0));
+        assert_eq!(nested(&out.estack[0]), Some(1));
+        assert_eq!(out.estack[1].id, Some(1));
+        assert_eq!(out.notifications[0].state.id, Some(2));
+        assert_eq!(nested(&out.notifications[0].state), Some(0));
+    }
+
+    #[test]
+    fn aliasing_and_map_order() {
+        // Struct copied into array vs the same struct.
+        let copied = halted(
+            r#"[{"type": "Array", "id": 1, "value": [{"type": "Struct", "id": 2, "value": []}]},
+                {"type": "Struct", "id": 3, "value": []}]"#,
+        );
+        let shared = halted(
+            r#"[{"type": "Array", "id": 1, "value": [{"type": "Struct", "id": 2, "value": []}]},
+                {"type": "Struct", "id": 2, "value": []}]"#,
+        );
+        assert_eq!(
+            copied.diff_halted(&shared, true),
+            Some("different aliasing")
+        );
+        assert_eq!(copied.diff_halted(&shared, false), None);
+        // Ids are not compared if one of harnesses doesn't print them.
+        let unnamed = halted(
+            r#"[{"type": "Array", "value": [{"type": "Struct", "value": []}]},
+                {"type": "Struct", "value": []}]"#,
+        );
+        assert_eq!(copied.diff_halted(&unnamed, false), None);
+        let map = |fst: &str, snd: &str| {
+            halted(&format!(
+                r#"[{{"type": "Map", "id": 1, "value": [
+                    {{"key": {{"type": "Integer", "value": "{fst}"}}, "value": {{"type": "Boolean", "value": true}}}},
+                    {{"key": {{"type": "Integer", "value": "{snd}"}}, "value": {{"type": "Boolean", "value": true}}}}]}}]"#
+            ))
+        };
+        assert_eq!(
+            map("1", "2").diff_halted(&map("2", "1"), true),
+            Some("different map order")
+        );
+        assert_eq!(
+            map("1", "2").diff_halted(&map("1", "3"), true),
+            Some("different stack")
+        );
+        assert_eq!(map("1", "2").diff_halted(&map("1", "2"), true), None);
+        assert_eq!(
+            map("1", "2").diff_halted(&map("2", "1"), false),
+            Some("different map order")
+        );
+    }
+
+    #[test]
+    fn map_violations() {
+        let map = |key: &str| {
+            halted(&format!(
+                r#"[{{"type": "Array", "value": [{{"type": "Map", "value": [
+                    {{"key": {key}, "value": {{"type": "Any", "value": null}}}}]}}]}}]"#
+            ))
+        };
+        assert_eq!(
+            map(r#"{"type": "Integer", "value": "1"}"#).violation(),
+            None
+        );
+        assert_eq!(
+            map(r#"{"type": "Buffer", "value": ""}"#).violation(),
+            Some(String::from("map key of type Buffer"))
+        );
+        let long = BASE64_STANDARD.encode([0; MAX_KEY_SIZE + 1]);
+        assert_eq!(
+            map(&format!(r#"{{"type": "ByteString", "value": "{long}"}}"#)).violation(),
+            Some(format!("map key of {} bytes", MAX_KEY_SIZE + 1))
+        );
+        let duplicate = halted(
+            r#"[{"type": "Map", "value": [
+                {"key": {"type": "Boolean", "value": true}, "value": {"type": "Any", "value": null}},
+                {"key": {"type": "Boolean", "value": true}, "value": {"type": "Any", "value": null}}]}]"#,
+        );
+        assert_eq!(
+            duplicate.violation(),
+            Some(String::from("duplicate map key true"))
+        );
+    }
 }
@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+
+use libafl::{
+    corpus::{Corpus, CorpusId},
+    inputs::UsesInput,
+    mutators::{MutationResult, Mutator},
+    random_corpus_id,
+    state::{HasCorpus, HasRand},
+    Error,
+};
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    input::{ByteCodeInput, ProgramInput},
+    item::{Item, ItemGenerator},
+    limits::Limits,
+};
+
+/// Probability to change the set of scripts instead of one of them.
+const CALLEES_PROBABILITY: f64 = 0.1;
+
+/// Probability to change arguments instead of scripts.
+const ARGS_PROBABILITY: f64 = 0.2;
+
+/// Probability to change limits instead of scripts.
+const LIMITS_PROBABILITY: f64 = 0.05;
+
+/// Applies script mutator to entry or one of callee scripts, sometimes adds, removes or swaps callees
+/// or changes initial stack items or execution limits instead.
+#[derive(Debug, Clone)]
+pub struct ProgramMutator<M> {
+    scripts: M,
+    max_callees: usize,
+    max_args: usize,
+    items: ItemGenerator,
+    limits: Option<Limits>,
+}
+
+impl<M> ProgramMutator<M> {
+    /// Programs never get more than `max_callees` callees and `max_args` arguments from mutations
+    /// (none if it is zero).
+    pub fn new(scripts: M, max_callees: usize, max_args: usize) -> Self {
+        Self {
+            scripts,
+            max_callees,
+            max_args,
+            items: ItemGenerator::default(),
+            limits: None,
+        }
+    }
+
+    /// Also mutate limits of programs around given profile.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+}
+
+impl<M> Named for ProgramMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ProgramMutator");
+        &NAME
+    }
+}
+
+impl<M, S> Mutator<ProgramInput, S> for ProgramMutator<M>
+where
+    M: Mutator<ByteCodeInput, S>,
+    S: HasRand + HasCorpus + UsesInput<Input = ProgramInput>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut ProgramInput) -> Result<MutationResult, Error> {
+        if self.max_callees > 0 && state.rand_mut().coinflip(CALLEES_PROBABILITY) {
+            return self.mutate_callees(state, input);
+        }
+        if self.max_args > 0 && state.rand_mut().coinflip(ARGS_PROBABILITY) {
+            return Ok(self.mutate_args(state.rand_mut(), input));
+        }
+        if let Some(base) = &self.limits {
+            let rand = state.rand_mut();
+            if rand.coinflip(LIMITS_PROBABILITY) {
+                match &mut input.limits {
+                    Some(_) if rand.coinflip(0.1) => input.limits = None,
+                    limits => limits.get_or_insert(*base).mutate(rand, base),
+                }
+                return Ok(MutationResult::Mutated);
+            }
+        }
+        let script = match state.rand_mut().below(input.callees.len() + 1) {
+            0 => &mut input.entry,
+            i => &mut input.callees[i - 1],
+        };
+        self.scripts.mutate(state, script)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.scripts.post_exec(state, new_corpus_id)
+    }
+}
+
+impl<M> ProgramMutator<M> {
+    fn mutate_callees<S>(
+        &self,
+        state: &mut S,
+        input: &mut ProgramInput,
+    ) -> Result<MutationResult, Error>
+    where
+        S: HasRand + HasCorpus + UsesInput<Input = ProgramInput>,
+    {
+        let callees = input.callees.len();
+        match state.rand_mut().below(3) {
+            0 if callees < self.max_callees => {
+                let id = random_corpus_id!(state.corpus(), state.rand_mut());
+                let callee = state.corpus().cloned_input_for_id(id)?.entry;
+                let at = state.rand_mut().below(callees + 1);
+                input.callees.insert(at, callee);
+            }
+            1 if callees > 0 => {
+                input.callees.remove(state.rand_mut().below(callees));
+            }
+            2 if callees > 0 => {
+                let i = state.rand_mut().below(callees);
+                std::mem::swap(&mut input.entry, &mut input.callees[i]);
+            }
+            _ => return Ok(MutationResult::Skipped),
+        }
+        Ok(MutationResult::Mutated)
+    }
+
+    /// Pushes, removes, swaps or wraps arguments or replaces one of them (or its element) with a new item.
+    fn mutate_args<R: Rand>(&self, rand: &mut R, input: &mut ProgramInput) -> MutationResult {
+        let args = input.args.len();
+        match rand.below(5) {
+            0 if args < self.max_args => {
+                let item = self.items.item(rand, 0);
+                let at = rand.below(args + 1);
+                input.args.insert(at, item);
+            }
+            1 if args > 0 => {
+                input.args.remove(rand.below(args));
+            }
+            2 if args > 1 => {
+                let (i, j) = (rand.below(args), rand.below(args));
+                input.args.swap(i, j);
+            }
+            3 if args > 0 => {
+                let i = rand.below(args);
+                let item = std::mem::replace(&mut input.args[i], Item::Null);
+                input.args[i] = match rand.coinflip(0.5) {
+                    true => Item::Array(vec![item]),
+                    false => Item::Struct(vec![item]),
+                };
+            }
+            4 if args > 0 => {
+                let i = rand.below(args);
+                let item = self.items.item(rand, 0);
+                *input.args[i].pick_mut(rand) = item;
+            }
+            _ => return MutationResult::Skipped,
+        }
+        MutationResult::Mutated
+    }
+}
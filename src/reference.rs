@@ -0,0 +1,1076 @@
+//! Reference interpreter of a subset of NeoVM (push, jump, stack, arithmetic, bitwise and compound-type opcodes)
+//! following semantics of the C# implementation. It breaks ties between VMs: scripts using anything else
+//! (calls, slots, exceptions, syscalls, conversions...) are not supported.
+
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    dictionary::MAX_INTEGER_SIZE,
+    input::ProgramInput,
+    item::{
+        Item, ANY, ARRAY, BOOLEAN, BUFFER, BYTESTRING, INTEGER, MAP, MAX_KEY_SIZE, STRUCT, TYPES,
+    },
+    limits::Limits,
+    opcode::{self, *},
+    output::{Output, StackItem},
+};
+
+/// Scripts running longer are not supported (they probably loop).
+const MAX_STEPS: usize = 100_000;
+
+/// How the reference interpreter finished the script.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Halt(Vec<StackItem>),
+    Fault,
+}
+
+impl Outcome {
+    pub fn matches(&self, output: &Output) -> bool {
+        match self {
+            Outcome::Halt(estack) => {
+                output.status == "VM halted" && output.normalized_estack(false) == *estack
+            }
+            Outcome::Fault => output.status != "VM halted",
+        }
+    }
+}
+
+/// Runs entry script of a program without callees, `None` if it is not supported.
+pub fn run(input: &ProgramInput, limits: &Limits) -> Option<Outcome> {
+    if !input.callees.is_empty() {
+        return None;
+    }
+    let mut vm = Vm {
+        script: &input.entry.opcodes,
+        limits,
+        stack: input.args.iter().map(Value::from_item).collect(),
+    };
+    match vm.execute() {
+        Ok(()) => {
+            let mut estack = Vec::new();
+            // Harnesses print the top item first.
+            for value in vm.stack.iter().rev() {
+                estack.push(value.to_item(&mut Vec::new())?.to_stack_item());
+            }
+            Some(Outcome::Halt(estack))
+        }
+        Err(Stop::Fault) => Some(Outcome::Fault),
+        Err(Stop::Unsupported) => None,
+    }
+}
+
+enum Stop {
+    Fault,
+    Unsupported,
+}
+
+type Result<T> = std::result::Result<T, Stop>;
+
+type Items = Rc<RefCell<Vec<Value>>>;
+
+#[derive(Debug, Clone)]
+enum Value {
+    Null,
+    Boolean(bool),
+    Integer(BigInt),
+    ByteString(Rc<[u8]>),
+    Buffer(Rc<RefCell<Vec<u8>>>),
+    Array(Items),
+    Struct(Items),
+    Map(Rc<RefCell<Vec<(Value, Value)>>>),
+}
+
+impl Value {
+    fn from_item(item: &Item) -> Value {
+        let items =
+            |items: &[Item]| Rc::new(RefCell::new(items.iter().map(Value::from_item).collect()));
+        match item {
+            Item::Null => Value::Null,
+            Item::Boolean(value) => Value::Boolean(*value),
+            Item::Integer(value) => Value::Integer(BigInt::from_signed_bytes_le(value)),
+            Item::ByteString(value) => Value::ByteString(value.as_slice().into()),
+            Item::Buffer(value) => Value::Buffer(Rc::new(RefCell::new(value.clone()))),
+            Item::Array(values) => Value::Array(items(values)),
+            Item::Struct(values) => Value::Struct(items(values)),
+            Item::Map(pairs) => Value::Map(Rc::new(RefCell::new(
+                pairs
+                    .iter()
+                    .map(|(key, value)| (Value::from_item(key), Value::from_item(value)))
+                    .collect(),
+            ))),
+        }
+    }
+
+    /// `None` for recursive compound items, harnesses can't print them.
+    fn to_item(&self, parents: &mut Vec<*const ()>) -> Option<Item> {
+        let ptr = match self {
+            Value::Array(items) | Value::Struct(items) => Rc::as_ptr(items) as *const (),
+            Value::Map(pairs) => Rc::as_ptr(pairs) as *const (),
+            _ => std::ptr::null(),
+        };
+        if !ptr.is_null() {
+            if parents.contains(&ptr) {
+                return None;
+            }
+            parents.push(ptr);
+        }
+        let item = match self {
+            Value::Null => Item::Null,
+            Value::Boolean(value) => Item::Boolean(*value),
+            Value::Integer(value) => Item::Integer(value.to_signed_bytes_le()),
+            Value::ByteString(value) => Item::ByteString(value.to_vec()),
+            Value::Buffer(value) => Item::Buffer(value.borrow().clone()),
+            Value::Array(items) | Value::Struct(items) => {
+                let items = items
+                    .borrow()
+                    .iter()
+                    .map(|item| item.to_item(parents))
+                    .collect::<Option<Vec<_>>>()?;
+                match self {
+                    Value::Array(_) => Item::Array(items),
+                    _ => Item::Struct(items),
+                }
+            }
+            Value::Map(pairs) => Item::Map(
+                pairs
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| Some((key.to_item(parents)?, value.to_item(parents)?)))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+        };
+        if !ptr.is_null() {
+            parents.pop();
+        }
+        Some(item)
+    }
+
+    fn type_code(&self) -> u8 {
+        match self {
+            Value::Null => ANY,
+            Value::Boolean(_) => BOOLEAN,
+            Value::Integer(_) => INTEGER,
+            Value::ByteString(_) => BYTESTRING,
+            Value::Buffer(_) => BUFFER,
+            Value::Array(_) => ARRAY,
+            Value::Struct(_) => STRUCT,
+            Value::Map(_) => MAP,
+        }
+    }
+
+    fn is_primitive(&self) -> bool {
+        matches!(
+            self,
+            Value::Boolean(_) | Value::Integer(_) | Value::ByteString(_)
+        )
+    }
+
+    /// Bytes of primitive item.
+    fn span(&self) -> Result<Vec<u8>> {
+        match self {
+            Value::Boolean(value) => Ok(vec![*value as u8]),
+            Value::Integer(value) if value.is_zero() => Ok(Vec::new()),
+            Value::Integer(value) => Ok(value.to_signed_bytes_le()),
+            Value::ByteString(value) => Ok(value.to_vec()),
+            _ => Err(Stop::Fault),
+        }
+    }
+
+    fn integer(&self) -> Result<BigInt> {
+        match self {
+            Value::Integer(value) => Ok(value.clone()),
+            Value::Boolean(value) => Ok(BigInt::from(*value as u8)),
+            Value::ByteString(value) if value.len() > MAX_INTEGER_SIZE => Err(Stop::Fault),
+            Value::ByteString(value) => Ok(BigInt::from_signed_bytes_le(value)),
+            Value::Buffer(_) => Err(Stop::Unsupported),
+            _ => Err(Stop::Fault),
+        }
+    }
+
+    fn boolean(&self) -> Result<bool> {
+        match self {
+            Value::Null => Ok(false),
+            Value::Boolean(value) => Ok(*value),
+            Value::Integer(value) => Ok(!value.is_zero()),
+            Value::ByteString(value) if value.len() > MAX_INTEGER_SIZE => Err(Stop::Fault),
+            Value::ByteString(value) => Ok(value.iter().any(|byte| *byte != 0)),
+            _ => Ok(true),
+        }
+    }
+
+    fn equals(&self, other: &Value, limits: &Limits) -> Result<bool> {
+        Ok(match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::ByteString(a), Value::ByteString(b)) => {
+                if a.len().max(b.len()) as u64 > limits.max_comparable_size {
+                    return Err(Stop::Unsupported);
+                }
+                a == b
+            }
+            (Value::Buffer(a), Value::Buffer(b)) => Rc::ptr_eq(a, b),
+            (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            // Struct comparison has its own limits.
+            (Value::Struct(_), Value::Struct(_)) => return Err(Stop::Unsupported),
+            _ => false,
+        })
+    }
+
+    /// Structs are copied by value when stored, nested structs are copied too.
+    fn cloned(self) -> Value {
+        match self {
+            Value::Struct(items) => Value::Struct(Rc::new(RefCell::new(
+                items.borrow().iter().cloned().map(Value::cloned).collect(),
+            ))),
+            value => value,
+        }
+    }
+}
+
+fn new_items(items: Vec<Value>) -> Items {
+    Rc::new(RefCell::new(items))
+}
+
+fn check_key(key: &Value) -> Result<()> {
+    if !key.is_primitive() || key.span()?.len() > MAX_KEY_SIZE {
+        return Err(Stop::Fault);
+    }
+    Ok(())
+}
+
+fn find_key(pairs: &[(Value, Value)], key: &Value, limits: &Limits) -> Result<Option<usize>> {
+    for (i, (other, _)) in pairs.iter().enumerate() {
+        if other.equals(key, limits)? {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+/// Index of array, buffer or byte string: C# casts it to `int`, so it faults outside of `i32` range.
+fn int_index(key: &Value) -> Result<usize> {
+    key.integer()?
+        .to_i32()
+        .and_then(|index| usize::try_from(index).ok())
+        .ok_or(Stop::Fault)
+}
+
+fn index(key: &Value, len: usize) -> Result<usize> {
+    Some(int_index(key)?)
+        .filter(|index| *index < len)
+        .ok_or(Stop::Fault)
+}
+
+/// Result of `BigInteger.ModPow`: sign of `value ^ exponent`, magnitude modulo `|modulus|`.
+fn mod_pow(value: &BigInt, exponent: &BigInt, modulus: &BigInt) -> Result<BigInt> {
+    if exponent.is_negative() || modulus.is_zero() {
+        return Err(Stop::Fault);
+    }
+    let magnitude = value
+        .magnitude()
+        .modpow(exponent.magnitude(), modulus.magnitude());
+    let negative = value.is_negative() && exponent.bit(0);
+    Ok(if negative {
+        -BigInt::from(magnitude)
+    } else {
+        BigInt::from(magnitude)
+    })
+}
+
+/// `ModInverse` of Neo: value must be positive and modulus at least 2.
+fn mod_inverse(value: &BigInt, modulus: &BigInt) -> Result<BigInt> {
+    if !value.is_positive() || *modulus < BigInt::from(2) {
+        return Err(Stop::Fault);
+    }
+    let (mut r, mut old_r) = (value.clone(), modulus.clone());
+    let (mut s, mut old_s) = (BigInt::one(), BigInt::zero());
+    while r.is_positive() {
+        let q = &old_r / &r;
+        (old_r, r) = (r.clone(), &old_r % &r);
+        (old_s, s) = (s.clone(), &old_s - &q * &s);
+    }
+    let mut result = old_s % modulus;
+    if result.is_negative() {
+        result += modulus;
+    }
+    if !(value * &result % modulus).is_one() {
+        return Err(Stop::Fault);
+    }
+    Ok(result)
+}
+
+struct Vm<'a> {
+    script: &'a [u8],
+    limits: &'a Limits,
+    /// Top item is the last one.
+    stack: Vec<Value>,
+}
+
+impl Vm<'_> {
+    fn execute(&mut self) -> Result<()> {
+        let mut ip = 0;
+        for _ in 0..MAX_STEPS {
+            if ip >= self.script.len() {
+                return Ok(());
+            }
+            let Some(instr) = opcode::instructions(&self.script[ip..]).next() else {
+                return Err(Stop::Fault);
+            };
+            let code = self.script[ip];
+            if code == RET {
+                return Ok(());
+            }
+            let next = ip + instr.size();
+            ip = match self.jump(code, instr.operand)? {
+                Some(offset) => usize::try_from(ip as i64 + offset)
+                    .ok()
+                    .filter(|to| *to <= self.script.len())
+                    .ok_or(Stop::Fault)?,
+                None => {
+                    self.step(code, instr.operand)?;
+                    next
+                }
+            };
+            self.check_references()?;
+        }
+        Err(Stop::Unsupported)
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        self.stack.pop().ok_or(Stop::Fault)
+    }
+
+    fn pop_int(&mut self) -> Result<BigInt> {
+        self.pop()?.integer()
+    }
+
+    fn pop_bool(&mut self) -> Result<bool> {
+        self.pop()?.boolean()
+    }
+
+    /// Count, index or shift that must not be negative.
+    fn pop_count(&mut self) -> Result<usize> {
+        self.pop_int()?.to_usize().ok_or(Stop::Fault)
+    }
+
+    fn pop_shift(&mut self) -> Result<usize> {
+        let shift = self.pop_count()?;
+        if shift as u64 > self.limits.max_shift {
+            return Err(Stop::Fault);
+        }
+        Ok(shift)
+    }
+
+    fn peek(&self, n: usize) -> Result<Value> {
+        n.checked_add(1)
+            .and_then(|n| self.stack.len().checked_sub(n))
+            .map(|i| self.stack[i].clone())
+            .ok_or(Stop::Fault)
+    }
+
+    /// Removes item `n` positions below the top.
+    fn remove(&mut self, n: usize) -> Result<Value> {
+        let i = self.stack.len().checked_sub(n + 1).ok_or(Stop::Fault)?;
+        Ok(self.stack.remove(i))
+    }
+
+    fn reverse(&mut self, n: usize) -> Result<()> {
+        let start = self.stack.len().checked_sub(n).ok_or(Stop::Fault)?;
+        self.stack[start..].reverse();
+        Ok(())
+    }
+
+    fn push_int(&mut self, value: BigInt) -> Result<()> {
+        if value.to_signed_bytes_le().len() > MAX_INTEGER_SIZE {
+            return Err(Stop::Fault);
+        }
+        self.stack.push(Value::Integer(value));
+        Ok(())
+    }
+
+    fn push_bool(&mut self, value: bool) -> Result<()> {
+        self.stack.push(Value::Boolean(value));
+        Ok(())
+    }
+
+    /// Relative offset if the instruction is a jump that is taken, `None` for other instructions.
+    fn jump(&mut self, code: u8, operand: &[u8]) -> Result<Option<i64>> {
+        let offset = || match operand.len() {
+            1 => operand[0] as i8 as i64,
+            _ => i32::from_le_bytes(operand.try_into().unwrap()) as i64,
+        };
+        let taken = match code {
+            JMP | JMP_L => true,
+            JMPIF | JMPIF_L => self.pop_bool()?,
+            JMPIFNOT | JMPIFNOT_L => !self.pop_bool()?,
+            JMPEQ | JMPEQ_L | JMPNE | JMPNE_L | JMPGT | JMPGT_L | JMPGE | JMPGE_L | JMPLT
+            | JMPLT_L | JMPLE | JMPLE_L => {
+                let x2 = self.pop_int()?;
+                let x1 = self.pop_int()?;
+                match code {
+                    JMPEQ | JMPEQ_L => x1 == x2,
+                    JMPNE | JMPNE_L => x1 != x2,
+                    JMPGT | JMPGT_L => x1 > x2,
+                    JMPGE | JMPGE_L => x1 >= x2,
+                    JMPLT | JMPLT_L => x1 < x2,
+                    _ => x1 <= x2,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(if taken {
+            offset()
+        } else {
+            1 + operand.len() as i64
+        }))
+    }
+
+    fn step(&mut self, code: u8, operand: &[u8]) -> Result<()> {
+        let limits = self.limits;
+        match code {
+            PUSHINT8 | PUSHINT16 | PUSHINT32 | PUSHINT64 | PUSHINT128 | PUSHINT256 => {
+                self.push_int(BigInt::from_signed_bytes_le(operand))?
+            }
+            PUSHT | PUSHF => self.push_bool(code == PUSHT)?,
+            PUSHNULL => self.stack.push(Value::Null),
+            PUSHDATA1 | PUSHDATA2 | PUSHDATA4 => {
+                let prefix = 1 << (code - PUSHDATA1);
+                let data = &operand[prefix..];
+                if data.len() as u64 > limits.max_item_size {
+                    return Err(Stop::Fault);
+                }
+                self.stack.push(Value::ByteString(data.into()));
+            }
+            PUSHM1..=PUSH16 => self.push_int(BigInt::from(code as i64 - PUSH0 as i64))?,
+            NOP => (),
+            ABORT | THROW => return Err(Stop::Fault),
+            ABORTMSG => {
+                self.pop()?;
+                return Err(Stop::Fault);
+            }
+            ASSERT => {
+                if !self.pop_bool()? {
+                    return Err(Stop::Fault);
+                }
+            }
+            ASSERTMSG => {
+                // Message is read with `GetSpan`, it faults even if the assertion holds.
+                match self.pop()? {
+                    Value::Buffer(_) => (),
+                    message => {
+                        message.span()?;
+                    }
+                }
+                if !self.pop_bool()? {
+                    return Err(Stop::Fault);
+                }
+            }
+
+            DEPTH => self.push_int(BigInt::from(self.stack.len()))?,
+            DROP => {
+                self.pop()?;
+            }
+            NIP => {
+                self.remove(1)?;
+            }
+            XDROP => {
+                let n = self.pop_count()?;
+                self.remove(n)?;
+            }
+            CLEAR => self.stack.clear(),
+            DUP => self.stack.push(self.peek(0)?),
+            OVER => self.stack.push(self.peek(1)?),
+            PICK => {
+                let n = self.pop_count()?;
+                self.stack.push(self.peek(n)?);
+            }
+            TUCK => {
+                let top = self.peek(0)?;
+                let i = self.stack.len().checked_sub(2).ok_or(Stop::Fault)?;
+                self.stack.insert(i, top);
+            }
+            SWAP | ROT => {
+                let item = self.remove(if code == SWAP { 1 } else { 2 })?;
+                self.stack.push(item);
+            }
+            ROLL => {
+                let n = self.pop_count()?;
+                if n > 0 {
+                    let item = self.remove(n)?;
+                    self.stack.push(item);
+                }
+            }
+            REVERSE3 => self.reverse(3)?,
+            REVERSE4 => self.reverse(4)?,
+            REVERSEN => {
+                let n = self.pop_count()?;
+                self.reverse(n)?;
+            }
+
+            INVERT => {
+                let x = self.pop_int()?;
+                self.push_int(!x)?;
+            }
+            AND | OR | XOR => {
+                let x2 = self.pop_int()?;
+                let x1 = self.pop_int()?;
+                self.push_int(match code {
+                    AND => x1 & x2,
+                    OR => x1 | x2,
+                    _ => x1 ^ x2,
+                })?;
+            }
+            EQUAL | NOTEQUAL => {
+                let x2 = self.pop()?;
+                let x1 = self.pop()?;
+                let equal = x1.equals(&x2, limits)?;
+                self.push_bool(equal == (code == EQUAL))?;
+            }
+
+            SIGN | ABS | NEGATE | INC | DEC | SQRT | NZ => {
+                let x = self.pop_int()?;
+                match code {
+                    SIGN => self.push_int(x.signum())?,
+                    ABS => self.push_int(x.abs())?,
+                    NEGATE => self.push_int(-x)?,
+                    INC => self.push_int(x + 1)?,
+                    DEC => self.push_int(x - 1)?,
+                    SQRT if x.is_negative() => return Err(Stop::Fault),
+                    SQRT => self.push_int(x.sqrt())?,
+                    _ => self.push_bool(!x.is_zero())?,
+                }
+            }
+            ADD | SUB | MUL | DIV | MOD | MIN | MAX => {
+                let x2 = self.pop_int()?;
+                let x1 = self.pop_int()?;
+                if matches!(code, DIV | MOD) && x2.is_zero() {
+                    return Err(Stop::Fault);
+                }
+                self.push_int(match code {
+                    ADD => x1 + x2,
+                    SUB => x1 - x2,
+                    MUL => x1 * x2,
+                    DIV => x1 / x2,
+                    MOD => x1 % x2,
+                    MIN => x1.min(x2),
+                    _ => x1.max(x2),
+                })?;
+            }
+            POW => {
+                let exponent = self.pop_shift()?;
+                let value = self.pop_int()?;
+                self.push_int(num_traits::pow(value, exponent))?;
+            }
+            MODMUL => {
+                let modulus = self.pop_int()?;
+                let x2 = self.pop_int()?;
+                let x1 = self.pop_int()?;
+                if modulus.is_zero() {
+                    return Err(Stop::Fault);
+                }
+                self.push_int(x1 * x2 % modulus)?;
+            }
+            MODPOW => {
+                let modulus = self.pop_int()?;
+                let exponent = self.pop_int()?;
+                let value = self.pop_int()?;
+                let result = if exponent == -BigInt::one() {
+                    mod_inverse(&value, &modulus)?
+                } else {
+                    mod_pow(&value, &exponent, &modulus)?
+                };
+                self.push_int(result)?;
+            }
+            SHL | SHR => {
+                let shift = self.pop_shift()?;
+                if shift > 0 {
+                    let x = self.pop_int()?;
+                    self.push_int(if code == SHL { x << shift } else { x >> shift })?;
+                }
+            }
+            NOT => {
+                let x = self.pop_bool()?;
+                self.push_bool(!x)?;
+            }
+            BOOLAND | BOOLOR => {
+                let x2 = self.pop_bool()?;
+                let x1 = self.pop_bool()?;
+                self.push_bool(if code == BOOLAND { x1 && x2 } else { x1 || x2 })?;
+            }
+            NUMEQUAL | NUMNOTEQUAL => {
+                let x2 = self.pop_int()?;
+                let x1 = self.pop_int()?;
+                self.push_bool((x1 == x2) == (code == NUMEQUAL))?;
+            }
+            LT | LE | GT | GE => {
+                let x2 = self.pop()?;
+                let x1 = self.pop()?;
+                if matches!(x1, Value::Null) || matches!(x2, Value::Null) {
+                    return self.push_bool(false);
+                }
+                let (x1, x2) = (x1.integer()?, x2.integer()?);
+                self.push_bool(match code {
+                    LT => x1 < x2,
+                    LE => x1 <= x2,
+                    GT => x1 > x2,
+                    _ => x1 >= x2,
+                })?;
+            }
+            WITHIN => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                let x = self.pop_int()?;
+                self.push_bool(a <= x && x < b)?;
+            }
+
+            PACK | PACKSTRUCT => {
+                let n = self.pop_count()?;
+                if n > self.stack.len() {
+                    return Err(Stop::Fault);
+                }
+                let items = new_items((0..n).map(|_| self.stack.pop().unwrap()).collect());
+                self.stack.push(if code == PACK {
+                    Value::Array(items)
+                } else {
+                    Value::Struct(items)
+                });
+            }
+            PACKMAP => {
+                let n = self.pop_count()?;
+                if n.checked_mul(2).is_none_or(|size| size > self.stack.len()) {
+                    return Err(Stop::Fault);
+                }
+                let mut pairs = Vec::new();
+                for _ in 0..n {
+                    let key = self.pop()?;
+                    check_key(&key)?;
+                    let value = self.pop()?;
+                    match find_key(&pairs, &key, limits)? {
+                        Some(i) => pairs[i].1 = value,
+                        None => pairs.push((key, value)),
+                    }
+                }
+                self.stack.push(Value::Map(Rc::new(RefCell::new(pairs))));
+            }
+            UNPACK => match self.pop()? {
+                Value::Array(items) | Value::Struct(items) => {
+                    let items = items.borrow().clone();
+                    let count = items.len();
+                    self.stack.extend(items.into_iter().rev());
+                    self.push_int(BigInt::from(count))?;
+                }
+                Value::Map(pairs) => {
+                    let pairs = pairs.borrow().clone();
+                    let count = pairs.len();
+                    for (key, value) in pairs.into_iter().rev() {
+                        self.stack.extend([value, key]);
+                    }
+                    self.push_int(BigInt::from(count))?;
+                }
+                _ => return Err(Stop::Fault),
+            },
+            NEWARRAY0 => self.stack.push(Value::Array(new_items(Vec::new()))),
+            NEWSTRUCT0 => self.stack.push(Value::Struct(new_items(Vec::new()))),
+            NEWARRAY | NEWARRAY_T | NEWSTRUCT => {
+                let n = self.pop_count()?;
+                if n as u64 > limits.max_stack_size {
+                    return Err(Stop::Fault);
+                }
+                let item = match code {
+                    NEWARRAY_T if !TYPES.contains(&operand[0]) => return Err(Stop::Fault),
+                    NEWARRAY_T => match operand[0] {
+                        BOOLEAN => Value::Boolean(false),
+                        INTEGER => Value::Integer(BigInt::zero()),
+                        BYTESTRING => Value::ByteString(Rc::from([])),
+                        _ => Value::Null,
+                    },
+                    _ => Value::Null,
+                };
+                let items = new_items(vec![item; n]);
+                self.stack.push(if code == NEWSTRUCT {
+                    Value::Struct(items)
+                } else {
+                    Value::Array(items)
+                });
+            }
+            NEWMAP => self
+                .stack
+                .push(Value::Map(Rc::new(RefCell::new(Vec::new())))),
+            SIZE => {
+                let size = match self.pop()? {
+                    Value::Array(items) | Value::Struct(items) => items.borrow().len(),
+                    Value::Map(pairs) => pairs.borrow().len(),
+                    Value::Buffer(buffer) => buffer.borrow().len(),
+                    value => value.span()?.len(),
+                };
+                self.push_int(BigInt::from(size))?;
+            }
+            HASKEY => {
+                let key = self.pop()?;
+                if !key.is_primitive() {
+                    return Err(Stop::Fault);
+                }
+                let x = self.pop()?;
+                let has = match &x {
+                    Value::Map(pairs) => {
+                        check_key(&key)?;
+                        find_key(&pairs.borrow(), &key, limits)?.is_some()
+                    }
+                    _ => {
+                        let index = int_index(&key)?;
+                        let len = match &x {
+                            Value::Array(items) | Value::Struct(items) => items.borrow().len(),
+                            Value::Buffer(buffer) => buffer.borrow().len(),
+                            Value::ByteString(bytes) => bytes.len(),
+                            _ => return Err(Stop::Fault),
+                        };
+                        index < len
+                    }
+                };
+                self.push_bool(has)?;
+            }
+            KEYS => match self.pop()? {
+                Value::Map(pairs) => {
+                    let keys = pairs.borrow().iter().map(|(key, _)| key.clone()).collect();
+                    self.stack.push(Value::Array(new_items(keys)));
+                }
+                _ => return Err(Stop::Fault),
+            },
+            VALUES => {
+                let values: Vec<Value> = match self.pop()? {
+                    Value::Array(items) | Value::Struct(items) => items.borrow().clone(),
+                    Value::Map(pairs) => pairs
+                        .borrow()
+                        .iter()
+                        .map(|(_, value)| value.clone())
+                        .collect(),
+                    _ => return Err(Stop::Fault),
+                };
+                let values = values.into_iter().map(Value::cloned).collect();
+                self.stack.push(Value::Array(new_items(values)));
+            }
+            PICKITEM => {
+                let key = self.pop()?;
+                if !key.is_primitive() {
+                    return Err(Stop::Fault);
+                }
+                let item = match self.pop()? {
+                    Value::Array(items) | Value::Struct(items) => {
+                        let items = items.borrow();
+                        items[index(&key, items.len())?].clone()
+                    }
+                    Value::Map(pairs) => {
+                        check_key(&key)?;
+                        let pairs = pairs.borrow();
+                        let i = find_key(&pairs, &key, limits)?.ok_or(Stop::Fault)?;
+                        pairs[i].1.clone()
+                    }
+                    Value::Buffer(buffer) => {
+                        let buffer = buffer.borrow();
+                        Value::Integer(BigInt::from(buffer[index(&key, buffer.len())?]))
+                    }
+                    value => {
+                        let span = value.span()?;
+                        Value::Integer(BigInt::from(span[index(&key, span.len())?]))
+                    }
+                };
+                self.stack.push(item);
+            }
+            APPEND => {
+                let item = self.pop()?.cloned();
+                match self.pop()? {
+                    Value::Array(items) | Value::Struct(items) => items.borrow_mut().push(item),
+                    _ => return Err(Stop::Fault),
+                }
+            }
+            SETITEM => {
+                let value = self.pop()?.cloned();
+                let key = self.pop()?;
+                if !key.is_primitive() {
+                    return Err(Stop::Fault);
+                }
+                match self.pop()? {
+                    Value::Array(items) | Value::Struct(items) => {
+                        let i = index(&key, items.borrow().len())?;
+                        items.borrow_mut()[i] = value;
+                    }
+                    Value::Map(pairs) => {
+                        check_key(&key)?;
+                        let found = find_key(&pairs.borrow(), &key, limits)?;
+                        match found {
+                            Some(i) => pairs.borrow_mut()[i].1 = value,
+                            None => pairs.borrow_mut().push((key, value)),
+                        }
+                    }
+                    Value::Buffer(buffer) => {
+                        let i = index(&key, buffer.borrow().len())?;
+                        if !value.is_primitive() {
+                            return Err(Stop::Fault);
+                        }
+                        let byte = value
+                            .integer()?
+                            .to_i64()
+                            .filter(|byte| (-128..=255).contains(byte))
+                            .ok_or(Stop::Fault)?;
+                        buffer.borrow_mut()[i] = byte as u8;
+                    }
+                    _ => return Err(Stop::Fault),
+                }
+            }
+            REVERSEITEMS => match self.pop()? {
+                Value::Array(items) | Value::Struct(items) => items.borrow_mut().reverse(),
+                Value::Buffer(buffer) => buffer.borrow_mut().reverse(),
+                _ => return Err(Stop::Fault),
+            },
+            REMOVE => {
+                let key = self.pop()?;
+                if !key.is_primitive() {
+                    return Err(Stop::Fault);
+                }
+                match self.pop()? {
+                    Value::Array(items) | Value::Struct(items) => {
+                        let i = index(&key, items.borrow().len())?;
+                        items.borrow_mut().remove(i);
+                    }
+                    Value::Map(pairs) => {
+                        check_key(&key)?;
+                        let found = find_key(&pairs.borrow(), &key, limits)?;
+                        if let Some(i) = found {
+                            pairs.borrow_mut().remove(i);
+                        }
+                    }
+                    _ => return Err(Stop::Fault),
+                }
+            }
+            CLEARITEMS => match self.pop()? {
+                Value::Array(items) | Value::Struct(items) => items.borrow_mut().clear(),
+                Value::Map(pairs) => pairs.borrow_mut().clear(),
+                _ => return Err(Stop::Fault),
+            },
+            POPITEM => match self.pop()? {
+                Value::Array(items) | Value::Struct(items) => {
+                    let item = items.borrow_mut().pop().ok_or(Stop::Fault)?;
+                    self.stack.push(item);
+                }
+                _ => return Err(Stop::Fault),
+            },
+
+            ISNULL => {
+                let x = self.pop()?;
+                self.push_bool(matches!(x, Value::Null))?;
+            }
+            ISTYPE => {
+                let x = self.pop()?;
+                if operand[0] == ANY || !TYPES.contains(&operand[0]) {
+                    return Err(Stop::Fault);
+                }
+                self.push_bool(x.type_code() == operand[0])?;
+            }
+            _ => return Err(Stop::Unsupported),
+        }
+        Ok(())
+    }
+
+    /// Reference counter of the VM counts stack slots and elements of reachable compound items.
+    fn check_references(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut references = self.stack.len();
+        let mut pending: Vec<Value> = self.stack.clone();
+        while let Some(value) = pending.pop() {
+            let children: Vec<Value> = match &value {
+                Value::Array(items) | Value::Struct(items) => {
+                    if !seen.insert(Rc::as_ptr(items) as *const ()) {
+                        continue;
+                    }
+                    items.borrow().clone()
+                }
+                Value::Map(pairs) => {
+                    if !seen.insert(Rc::as_ptr(pairs) as *const ()) {
+                        continue;
+                    }
+                    pairs
+                        .borrow()
+                        .iter()
+                        .flat_map(|(key, value)| [key.clone(), value.clone()])
+                        .collect()
+                }
+                _ => continue,
+            };
+            references += children.len();
+            pending.extend(children);
+        }
+        if references as u64 > self.limits.max_stack_size {
+            return Err(Stop::Fault);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, input::ByteCodeInput};
+
+    fn run_script(opcodes: Vec<u8>) -> Option<Outcome> {
+        run(
+            &ProgramInput::from(ByteCodeInput { opcodes }),
+            &Limits::default(),
+        )
+    }
+
+    fn run_asm(text: &str) -> Option<Outcome> {
+        run_script(assemble(text).unwrap())
+    }
+
+    /// Halted with given stack, top item first.
+    fn halt(items: &[Item]) -> Option<Outcome> {
+        Some(Outcome::Halt(
+            items.iter().map(Item::to_stack_item).collect(),
+        ))
+    }
+
+    fn int(value: i64) -> Item {
+        Item::Integer(BigInt::from(value).to_signed_bytes_le())
+    }
+
+    #[test]
+    fn arithmetic() {
+        // Division truncates toward zero, remainder has the sign of dividend.
+        assert_eq!(
+            run_asm("PUSH7\nPUSH2\nNEGATE\nDIV\nPUSH7\nNEGATE\nPUSH2\nMOD"),
+            halt(&[int(-1), int(-3)])
+        );
+        assert_eq!(run_asm("PUSH1\nPUSH0\nDIV"), Some(Outcome::Fault));
+        // Exponent -1 is modular inverse, result of ModPow has the sign of the value.
+        assert_eq!(run_asm("PUSH3\nPUSHM1\nPUSH7\nMODPOW"), halt(&[int(5)]));
+        assert_eq!(
+            run_asm("PUSH2\nNEGATE\nPUSH3\nPUSH5\nMODPOW"),
+            halt(&[int(-3)])
+        );
+        assert_eq!(
+            run_asm("PUSH2\nPUSHM1\nPUSH4\nMODPOW"),
+            Some(Outcome::Fault)
+        );
+    }
+
+    #[test]
+    fn integer_bounds() {
+        let max = format!("PUSHINT256 0x{}7f", "ff".repeat(31));
+        assert_eq!(
+            run_asm(&format!("{max}\nPUSH1\nSUB\nPUSH1\nADD")),
+            halt(&[Item::Integer(from_hex(&max[13..]))])
+        );
+        assert_eq!(run_asm(&format!("{max}\nPUSH1\nADD")), Some(Outcome::Fault));
+        assert_eq!(run_asm("PUSH0\nPUSHINT16 256\nSHL"), halt(&[int(0)]));
+        assert_eq!(run_asm("PUSH0\nPUSHINT16 257\nSHL"), Some(Outcome::Fault));
+        assert_eq!(run_asm("PUSH1\nPUSHINT16 256\nSHL"), Some(Outcome::Fault));
+        // 2^255 needs 33 bytes, but the shift is allowed.
+        assert!(matches!(
+            run_asm("PUSH1\nPUSHINT16 254\nSHL"),
+            Some(Outcome::Halt(_))
+        ));
+        assert_eq!(run_asm("PUSH1\nPUSHINT16 255\nSHL"), Some(Outcome::Fault));
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn compound() {
+        assert_eq!(
+            run_asm("PUSH1\nPUSH2\nPUSH2\nPACK"),
+            halt(&[Item::Array(vec![int(2), int(1)])])
+        );
+        assert_eq!(
+            run_asm("PUSH1\nPUSH2\nPUSH2\nPACK\nUNPACK"),
+            halt(&[int(2), int(2), int(1)])
+        );
+        // The last value of duplicate key wins.
+        assert_eq!(
+            run_asm("PUSH5\nPUSH1\nPUSH6\nPUSH1\nPUSH2\nPACKMAP"),
+            halt(&[Item::Map(vec![(int(1), int(5))])])
+        );
+        assert_eq!(
+            run_asm("NEWMAP\nDUP\nPUSH1\nPUSH2\nSETITEM"),
+            halt(&[Item::Map(vec![(int(1), int(2))])])
+        );
+        // Struct appended to array is a copy.
+        assert_eq!(
+            run_asm("NEWARRAY0\nNEWSTRUCT0\nOVER\nOVER\nAPPEND\nPUSH1\nAPPEND"),
+            halt(&[Item::Array(vec![Item::Struct(Vec::new())])])
+        );
+        assert_eq!(
+            run_asm("PUSH1\nNEWARRAY_T 0x21\nPUSH0\nPICKITEM"),
+            halt(&[int(0)])
+        );
+        assert_eq!(run_asm("PUSH1\nNEWARRAY_T 0x01"), Some(Outcome::Fault));
+        assert_eq!(
+            run_asm("PUSH0\nISTYPE 0x21\nPUSH0\nISTYPE 0x28"),
+            halt(&[Item::Boolean(false), Item::Boolean(true)])
+        );
+        assert_eq!(run_asm("PUSH0\nISTYPE 0x00"), Some(Outcome::Fault));
+    }
+
+    #[test]
+    fn faults() {
+        assert_eq!(
+            run_asm("PUSH1\nNEWARRAY\nPUSH0\nPICKITEM"),
+            halt(&[Item::Null])
+        );
+        assert_eq!(
+            run_asm("PUSH1\nNEWARRAY\nPUSH1\nPICKITEM"),
+            Some(Outcome::Fault)
+        );
+        assert_eq!(
+            run_asm("PUSH1\nNEWARRAY\nPUSHM1\nPICKITEM"),
+            Some(Outcome::Fault)
+        );
+        // Index is cast to `int`.
+        assert_eq!(
+            run_asm("PUSH1\nNEWARRAY\nPUSHINT32 2147483647\nHASKEY"),
+            halt(&[Item::Boolean(false)])
+        );
+        assert_eq!(
+            run_asm("PUSH1\nNEWARRAY\nPUSHINT64 2147483648\nHASKEY"),
+            Some(Outcome::Fault)
+        );
+        assert_eq!(
+            run_asm("PUSHDATA1 0x01\nPUSHINT64 4294967296\nPICKITEM"),
+            Some(Outcome::Fault)
+        );
+        // Message of ASSERTMSG must have a span.
+        assert_eq!(run_asm("PUSHT\nPUSHDATA1 \"ok\"\nASSERTMSG"), halt(&[]));
+        assert_eq!(run_asm("PUSHT\nNEWARRAY0\nASSERTMSG"), Some(Outcome::Fault));
+        assert_eq!(
+            run_asm("PUSHF\nPUSHDATA1 \"no\"\nASSERTMSG"),
+            Some(Outcome::Fault)
+        );
+        // Stack slot and elements count as references.
+        assert_eq!(
+            run_asm("PUSHINT16 2047\nNEWARRAY\nSIZE"),
+            halt(&[int(2047)])
+        );
+        assert_eq!(run_asm("PUSHINT16 2048\nNEWARRAY"), Some(Outcome::Fault));
+        assert_eq!(run_script(vec![0xff]), Some(Outcome::Fault));
+        assert_eq!(run_asm("DROP"), Some(Outcome::Fault));
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(run_asm("SYSCALL System.Runtime.Log"), None);
+        assert_eq!(run_asm("INITSLOT 1 0"), None);
+        // Array containing itself can't be printed.
+        assert_eq!(run_asm("NEWARRAY0\nDUP\nDUP\nAPPEND"), None);
+        assert_eq!(run_asm("JMP 0"), None);
+    }
+}
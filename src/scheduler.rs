@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use libafl::{
+    corpus::{Corpus, CorpusId},
+    inputs::UsesInput,
+    observers::ObserversTuple,
+    schedulers::Scheduler,
+    state::{HasCorpus, HasRand, UsesState},
+    Error, HasMetadata,
+};
+use libafl_bolts::rands::Rand;
+
+use crate::{feedback::last_op::LastOpMetadata, input::ProgramInput, opcode};
+
+/// Probability to pick input by target opcodes instead of asking inner scheduler.
+const TARGET_PROBABILITY: f64 = 0.5;
+
+/// Score of input that ends at one of target opcodes, each target instruction in script adds 1.
+const LASTOP_SCORE: usize = 4;
+
+/// Biases inner scheduler towards inputs with target opcodes (does nothing if there are no targets).
+/// Score is static: target instructions are counted in disassembled scripts, not how many times they are executed
+/// (harnesses only report the last executed opcode, it adds [`LASTOP_SCORE`]).
+pub struct TargetOpcodeScheduler<CS> {
+    inner: CS,
+    targets: Vec<u8>,
+    scores: BTreeMap<CorpusId, usize>,
+    total: usize,
+}
+
+impl<CS> TargetOpcodeScheduler<CS> {
+    pub fn new(inner: CS, targets: Vec<u8>) -> Self {
+        Self {
+            inner,
+            targets,
+            scores: BTreeMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Score inputs added since last call.
+    fn update_scores<S>(&mut self, state: &S) -> Result<(), Error>
+    where
+        S: HasCorpus + UsesInput<Input = ProgramInput>,
+    {
+        let corpus = state.corpus();
+        if corpus.count() == self.scores.len() {
+            return Ok(());
+        }
+        for id in corpus.ids() {
+            if self.scores.contains_key(&id) {
+                continue;
+            }
+            let testcase = corpus.get(id)?.borrow();
+            let mut score = testcase.input().as_ref().map_or(0, |input| {
+                input
+                    .scripts()
+                    .flat_map(|script| {
+                        opcode::instructions(&script.opcodes)
+                            .map(|instr| script.opcodes[instr.offset])
+                    })
+                    .filter(|code| self.targets.contains(code))
+                    .count()
+            });
+            if let Ok(metadata) = testcase.metadata::<LastOpMetadata>() {
+                if self.targets.contains(&metadata.lastop) {
+                    score += LASTOP_SCORE;
+                }
+            }
+            self.scores.insert(id, score);
+            self.total += score;
+        }
+        Ok(())
+    }
+}
+
+impl<CS> UsesState for TargetOpcodeScheduler<CS>
+where
+    CS: UsesState,
+{
+    type State = CS::State;
+}
+
+impl<CS> Scheduler for TargetOpcodeScheduler<CS>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasRand + UsesInput<Input = ProgramInput>,
+{
+    fn on_add(&mut self, state: &mut Self::State, id: CorpusId) -> Result<(), Error> {
+        self.inner.on_add(state, id)
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut Self::State,
+        input: &<Self::State as UsesInput>::Input,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<Self::State>,
+    {
+        self.inner.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        if !self.targets.is_empty() {
+            self.update_scores(state)?;
+            if self.total > 0 && state.rand_mut().coinflip(TARGET_PROBABILITY) {
+                let mut pick = state.rand_mut().below(self.total);
+                for (id, score) in &self.scores {
+                    if pick < *score {
+                        let id = *id;
+                        self.set_current_scheduled(state, Some(id))?;
+                        return Ok(id);
+                    }
+                    pick -= score;
+                }
+            }
+        }
+        self.inner.next(state)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut Self::State,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.inner.set_current_scheduled(state, next_id)
+    }
+}
@@ -0,0 +1,469 @@
+use std::{borrow::Cow, collections::BTreeSet, ops::Range};
+
+use libafl::{
+    corpus::Corpus,
+    inputs::HasMutatorBytes,
+    mutators::{MutationResult, Mutator},
+    random_corpus_id,
+    state::{HasCorpus, HasMaxSize, HasRand},
+    Error,
+};
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    input::ByteCodeInput,
+    opcode::{self, *},
+};
+
+/// Maximum number of instructions inserted or deleted at once.
+const MAX_INSTRUCTIONS: usize = 4;
+
+/// Relative offsets in operand of opcode as (position, size), they count from the start of instruction.
+pub fn jump_offsets(code: u8) -> &'static [(usize, usize)] {
+    match code {
+        JMP | JMPIF | JMPIFNOT | JMPEQ | JMPNE | JMPGT | JMPGE | JMPLT | JMPLE | CALL | ENDTRY => {
+            &[(0, 1)]
+        }
+        JMP_L | JMPIF_L | JMPIFNOT_L | JMPEQ_L | JMPNE_L | JMPGT_L | JMPGE_L | JMPLT_L
+        | JMPLE_L | CALL_L | ENDTRY_L | PUSHA => &[(0, 4)],
+        // Catch and finally offsets, zero means there is no such block.
+        TRY => &[(0, 1), (1, 1)],
+        TRY_L => &[(0, 4), (4, 4)],
+        _ => &[],
+    }
+}
+
+/// Offsets of instruction starts and the end of the last instruction.
+pub fn boundaries(script: &[u8]) -> Vec<usize> {
+    let mut boundaries = vec![0];
+    boundaries.extend(opcode::instructions(script).map(|instr| instr.offset + instr.size()));
+    boundaries
+}
+
+fn read_offset(bytes: &[u8]) -> i64 {
+    match bytes.len() {
+        1 => bytes[0] as i8 as i64,
+        _ => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+    }
+}
+
+/// Returns `None` if offset doesn't fit.
+pub fn write_offset(bytes: &mut [u8], offset: i64) -> Option<()> {
+    match bytes.len() {
+        1 => bytes[0] = i8::try_from(offset).ok()? as u8,
+        _ => bytes.copy_from_slice(&i32::try_from(offset).ok()?.to_le_bytes()),
+    }
+    Some(())
+}
+
+/// Rewrite jumps of instructions decoded from `script` and written to `out`.
+/// `moved` gives new offset of instruction (`None` to leave it alone),
+/// `target` gives new offset of jump target from `0..=script.len()` (`None` to keep the jump as is).
+/// Returns `false` if some offsets don't fit into operands anymore (they are left unchanged).
+fn relocate(
+    script: &[u8],
+    out: &mut [u8],
+    moved: impl Fn(usize) -> Option<usize>,
+    target: impl Fn(usize) -> Option<usize>,
+) -> bool {
+    let mut fits = true;
+    for instr in opcode::instructions(script) {
+        let Some(at) = moved(instr.offset) else {
+            continue;
+        };
+        for &(pos, size) in jump_offsets(script[instr.offset]) {
+            let operand = instr.offset + 1 + pos;
+            let offset = read_offset(&script[operand..operand + size]);
+            // Zero catch or finally offset is no block rather than the `TRY` itself, it stays zero.
+            if offset == 0 && matches!(script[instr.offset], TRY | TRY_L) {
+                continue;
+            }
+            let to = instr.offset as i64 + offset;
+            let Some(to) = usize::try_from(to)
+                .ok()
+                .filter(|to| *to <= script.len())
+                .and_then(&target)
+            else {
+                continue;
+            };
+            let operand = at + 1 + pos;
+            fits &=
+                write_offset(&mut out[operand..operand + size], to as i64 - at as i64).is_some();
+        }
+    }
+    fits
+}
+
+/// Replace instructions in `range` with `replacement` and fix jumps of the rest of the script.
+/// Jumps into the replaced range lead to its replacement, jumps to `range.end` lead to the instruction after replacement.
+/// Returns `None` if some short jump can't reach its target anymore.
+pub fn splice(script: &[u8], range: Range<usize>, replacement: &[u8]) -> Option<Vec<u8>> {
+    let shift = |offset: usize| {
+        if offset < range.start {
+            offset
+        } else if offset < range.end {
+            range.start
+        } else {
+            offset - range.len() + replacement.len()
+        }
+    };
+    let mut out = Vec::with_capacity(script.len() - range.len() + replacement.len());
+    out.extend_from_slice(&script[..range.start]);
+    out.extend_from_slice(replacement);
+    out.extend_from_slice(&script[range.end..]);
+    let fits = relocate(
+        script,
+        &mut out,
+        |offset| (!range.contains(&offset)).then(|| shift(offset)),
+        |to| Some(shift(to)),
+    );
+    fits.then_some(out)
+}
+
+/// Put `prefix` before instructions in `range` and `suffix` after them, fix jumps of the script.
+/// Jumps to `range.start` lead to the prefix, jumps to `range.end` lead to the suffix.
+/// Returns `None` if some short jump can't reach its target anymore.
+pub fn surround(
+    script: &[u8],
+    range: Range<usize>,
+    prefix: &[u8],
+    suffix: &[u8],
+) -> Option<Vec<u8>> {
+    let shift = |offset: usize| {
+        if offset <= range.start {
+            offset
+        } else if offset <= range.end {
+            offset + prefix.len()
+        } else {
+            offset + prefix.len() + suffix.len()
+        }
+    };
+    let mut out = Vec::with_capacity(script.len() + prefix.len() + suffix.len());
+    out.extend_from_slice(&script[..range.start]);
+    out.extend_from_slice(prefix);
+    out.extend_from_slice(&script[range.clone()]);
+    out.extend_from_slice(suffix);
+    out.extend_from_slice(&script[range.end..]);
+    // Instruction at `range.start` moves after the prefix, unlike jumps to it.
+    let moved = |offset: usize| match offset {
+        offset if offset < range.start => offset,
+        offset if offset < range.end => offset + prefix.len(),
+        offset => offset + prefix.len() + suffix.len(),
+    };
+    let fits = relocate(
+        script,
+        &mut out,
+        |offset| Some(moved(offset)),
+        |to| Some(shift(to)),
+    );
+    fits.then_some(out)
+}
+
+/// Copy of instructions in `range`, jumps leading outside of it lead to its end instead.
+/// Returns `None` if some short jump can't reach the end.
+pub fn fragment(script: &[u8], range: Range<usize>) -> Option<Vec<u8>> {
+    let mut out = script[range.clone()].to_vec();
+    let fits = relocate(
+        script,
+        &mut out,
+        |offset| range.contains(&offset).then(|| offset - range.start),
+        |to| (!(range.start..=range.end).contains(&to)).then_some(range.len()),
+    );
+    fits.then_some(out)
+}
+
+/// Ranges of instructions that are executed one after another: they start at jump targets and end after jumps.
+pub fn basic_blocks(script: &[u8]) -> Vec<Range<usize>> {
+    let boundaries = boundaries(script);
+    let mut leaders = BTreeSet::from([0]);
+    for instr in opcode::instructions(script) {
+        let code = script[instr.offset];
+        if !jump_offsets(code).is_empty()
+            || matches!(code, RET | THROW | ABORT | ABORTMSG | ENDFINALLY)
+        {
+            leaders.insert(instr.offset + instr.size());
+        }
+        for &(pos, size) in jump_offsets(code) {
+            let operand = instr.offset + 1 + pos;
+            let to = instr.offset as i64 + read_offset(&script[operand..operand + size]);
+            if let Ok(to) = usize::try_from(to) {
+                leaders.insert(to);
+            }
+        }
+    }
+    let end = *boundaries.last().unwrap();
+    let leaders: Vec<usize> = leaders
+        .into_iter()
+        .filter(|offset| *offset < end && boundaries.binary_search(offset).is_ok())
+        .chain([end])
+        .collect();
+    leaders.windows(2).map(|pair| pair[0]..pair[1]).collect()
+}
+
+/// Random range of 1 to `MAX_INSTRUCTIONS` whole instructions.
+pub fn random_range<R: Rand>(rand: &mut R, boundaries: &[usize]) -> Option<Range<usize>> {
+    if boundaries.len() < 2 {
+        return None;
+    }
+    let start = rand.below(boundaries.len() - 1);
+    let end = rand.between(
+        start + 1,
+        (start + MAX_INSTRUCTIONS).min(boundaries.len() - 1),
+    );
+    Some(boundaries[start]..boundaries[end])
+}
+
+/// Duplicates a few instructions of the script at another place, keeping jumps intact.
+#[derive(Debug, Clone, Default)]
+pub struct InstructionInsertMutator;
+
+impl Named for InstructionInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("InstructionInsertMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<ByteCodeInput, S> for InstructionInsertMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ByteCodeInput,
+    ) -> Result<MutationResult, Error> {
+        let boundaries = boundaries(&input.opcodes);
+        let rand = state.rand_mut();
+        let Some(range) = random_range(rand, &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let at = *rand.choose(&boundaries).unwrap();
+        if input.opcodes.len() + range.len() > state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        let Some(copy) = fragment(&input.opcodes, range) else {
+            return Ok(MutationResult::Skipped);
+        };
+        match splice(&input.opcodes, at..at, &copy) {
+            Some(opcodes) => {
+                input.opcodes = opcodes;
+                Ok(MutationResult::Mutated)
+            }
+            None => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+/// Removes a few instructions, keeping jumps intact.
+#[derive(Debug, Clone, Default)]
+pub struct InstructionDeleteMutator;
+
+impl Named for InstructionDeleteMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("InstructionDeleteMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<ByteCodeInput, S> for InstructionDeleteMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ByteCodeInput,
+    ) -> Result<MutationResult, Error> {
+        let boundaries = boundaries(&input.opcodes);
+        let Some(range) = random_range(state.rand_mut(), &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        match splice(&input.opcodes, range, &[]) {
+            Some(opcodes) => {
+                input.opcodes = opcodes;
+                Ok(MutationResult::Mutated)
+            }
+            None => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+/// Combines instructions of the script with instructions of another corpus entry, keeping jumps of both intact:
+/// inserts basic block or a few instructions of the other script, replaces a few own instructions with them
+/// or replaces own suffix with suffix of the other script.
+#[derive(Debug, Clone, Default)]
+pub struct CrossoverMutator;
+
+impl Named for CrossoverMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CrossoverMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<ByteCodeInput, S> for CrossoverMutator
+where
+    S: HasRand + HasMaxSize + HasCorpus,
+    S::Input: HasMutatorBytes,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ByteCodeInput,
+    ) -> Result<MutationResult, Error> {
+        let id = random_corpus_id!(state.corpus(), state.rand_mut());
+        if Some(id) == *state.corpus().current() {
+            return Ok(MutationResult::Skipped);
+        }
+        let other = state.corpus().cloned_input_for_id(id)?.bytes().to_vec();
+        let own = boundaries(&input.opcodes);
+        let others = boundaries(&other);
+        let rand = state.rand_mut();
+        let at = *rand.choose(&own).unwrap();
+        let (range, donor) = match rand.below(3) {
+            0 => {
+                let blocks = basic_blocks(&other);
+                let Some(block) = rand.choose(&blocks) else {
+                    return Ok(MutationResult::Skipped);
+                };
+                (at..at, block.clone())
+            }
+            1 => {
+                let (Some(range), Some(donor)) =
+                    (random_range(rand, &own), random_range(rand, &others))
+                else {
+                    return Ok(MutationResult::Skipped);
+                };
+                (range, donor)
+            }
+            _ => {
+                let from = *rand.choose(&others).unwrap();
+                (at..*own.last().unwrap(), from..*others.last().unwrap())
+            }
+        };
+        if donor.is_empty() || input.opcodes.len() - range.len() + donor.len() > state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        let Some(donor) = fragment(&other, donor) else {
+            return Ok(MutationResult::Skipped);
+        };
+        match splice(&input.opcodes, range, &donor) {
+            Some(opcodes) => {
+                input.opcodes = opcodes;
+                Ok(MutationResult::Mutated)
+            }
+            None => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `JMP` to `RET`, three `NOP`s, `JMP` back to the first `NOP`, `RET`.
+    const SCRIPT: &[u8] = &[JMP, 5, NOP, NOP, NOP, JMP, (-3i8) as u8, RET];
+
+    #[test]
+    fn insert() {
+        // Jump from before the insertion point to after it moves, jump from after it to before it gets longer.
+        assert_eq!(
+            splice(SCRIPT, 3..3, &[PUSH0, DROP]).unwrap(),
+            [JMP, 7, NOP, PUSH0, DROP, NOP, NOP, JMP, (-5i8) as u8, RET]
+        );
+        // Jump to the insertion point leads to the instruction after inserted ones.
+        assert_eq!(
+            splice(SCRIPT, 5..5, &[PUSH0, DROP]).unwrap(),
+            [JMP, 7, NOP, NOP, NOP, PUSH0, DROP, JMP, (-5i8) as u8, RET]
+        );
+        // Jumps inside of the inserted instructions are left alone.
+        assert_eq!(
+            splice(SCRIPT, 0..0, &[JMP, 2]).unwrap(),
+            [JMP, 2, JMP, 5, NOP, NOP, NOP, JMP, (-3i8) as u8, RET]
+        );
+    }
+
+    #[test]
+    fn delete() {
+        // Jump into deleted range leads to the instruction after it.
+        assert_eq!(
+            splice(SCRIPT, 2..4, &[]).unwrap(),
+            [JMP, 3, NOP, JMP, (-1i8) as u8, RET]
+        );
+        // Deleted jumps are not relocated.
+        assert_eq!(
+            splice(SCRIPT, 0..2, &[]).unwrap(),
+            [NOP, NOP, NOP, JMP, (-3i8) as u8, RET]
+        );
+    }
+
+    #[test]
+    fn short_jump_overflow() {
+        assert_eq!(splice(SCRIPT, 3..3, &[NOP; 130]), None);
+        let mut long = vec![JMP_L];
+        long.extend(7i32.to_le_bytes());
+        long.extend([NOP, NOP, RET]);
+        let spliced = splice(&long, 5..5, &[NOP; 130]).unwrap();
+        assert_eq!(spliced[1..5], 137i32.to_le_bytes());
+    }
+
+    #[test]
+    fn surround_keeps_range_targets() {
+        // Jumps to the start lead to the prefix, jumps to the end lead to the suffix.
+        let script = [JMP, 2, NOP, JMP, 2, RET];
+        assert_eq!(
+            surround(&script, 2..5, &[PUSH0], &[DROP, DROP]).unwrap(),
+            [JMP, 2, PUSH0, NOP, JMP, 2, DROP, DROP, RET]
+        );
+        let script = [NOP, JMP, (-1i8) as u8, JMP, 2, NOP, RET];
+        assert_eq!(
+            surround(&script, 0..5, &[PUSH0], &[DROP]).unwrap(),
+            [PUSH0, NOP, JMP, (-2i8) as u8, JMP, 2, DROP, NOP, RET]
+        );
+        // Jumps over the range, from inside of it to its end and from after it back to the start of the script.
+        let script = [JMP, 6, JMP, 2, NOP, RET, JMP, (-6i8) as u8];
+        assert_eq!(
+            surround(&script, 2..4, &[NOP], &[NOP]).unwrap(),
+            [JMP, 8, NOP, JMP, 2, NOP, NOP, RET, JMP, (-8i8) as u8]
+        );
+        assert_eq!(surround(&script, 2..4, &[NOP; 128], &[]), None);
+        // Zero catch offset of TRY at the start stays zero, the finally one follows the block.
+        let script = [TRY, 0, 4, NOP, ENDFINALLY, RET];
+        assert_eq!(
+            surround(&script, 0..3, &[NOP], &[NOP]).unwrap(),
+            [NOP, TRY, 0, 5, NOP, NOP, ENDFINALLY, RET]
+        );
+        let mut script = vec![TRY_L];
+        script.extend(10i32.to_le_bytes());
+        script.extend(0i32.to_le_bytes());
+        script.extend([NOP, ENDFINALLY, RET]);
+        let mut wrapped = vec![NOP, TRY_L];
+        wrapped.extend(11i32.to_le_bytes());
+        wrapped.extend(0i32.to_le_bytes());
+        wrapped.extend([NOP, NOP, ENDFINALLY, RET]);
+        assert_eq!(surround(&script, 0..9, &[NOP], &[NOP]).unwrap(), wrapped);
+    }
+
+    #[test]
+    fn fragments() {
+        // Jump leading outside of fragment leads to its end.
+        assert_eq!(fragment(SCRIPT, 5..8).unwrap(), [JMP, 3, RET]);
+        assert_eq!(fragment(SCRIPT, 0..8).unwrap(), SCRIPT);
+        // Jump leading to the end of fragment is kept.
+        assert_eq!(fragment(SCRIPT, 0..5).unwrap(), [JMP, 5, NOP, NOP, NOP]);
+        let mut script = vec![NOP, JMP, (-1i8) as u8];
+        script.extend([NOP; 200]);
+        assert_eq!(fragment(&script, 1..script.len()), None);
+    }
+
+    #[test]
+    fn try_offsets() {
+        // Catch and finally offsets of TRY are relocated separately, zero ones mean there is no block.
+        let script = [TRY, 4, 0, NOP, THROW, ENDTRY, 2, RET];
+        assert_eq!(
+            splice(&script, 4..4, &[NOP]).unwrap(),
+            [TRY, 5, 0, NOP, NOP, THROW, ENDTRY, 2, RET]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    marker::PhantomData,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use libafl::{
+    corpus::Corpus,
+    inputs::UsesInput,
+    stages::Stage,
+    state::{HasCorpus, UsesState},
+    Error,
+};
+
+use crate::{
+    corpus::{is_found_by, write_seeds},
+    feedback::FeedbackKind,
+    input::ProgramInput,
+};
+
+/// Periodically writes the queue to a file in the seed corpus format (does nothing if interval is not set).
+pub struct DumpCorpusStage<EM, Z> {
+    path: Box<Path>,
+    interval: Option<Duration>,
+    found_by: Option<FeedbackKind>,
+    last_dump: Instant,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> DumpCorpusStage<EM, Z> {
+    pub fn new(
+        path: Box<Path>,
+        interval: Option<Duration>,
+        found_by: Option<FeedbackKind>,
+    ) -> Self {
+        Self {
+            path,
+            interval,
+            found_by,
+            last_dump: Instant::now(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<EM, Z> UsesState for DumpCorpusStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for DumpCorpusStage<EM, Z>
+where
+    EM: UsesState,
+    EM::State: HasCorpus + UsesInput<Input = ProgramInput>,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        match self.interval {
+            Some(interval) if self.last_dump.elapsed() >= interval => (),
+            _ => return Ok(()),
+        }
+        self.last_dump = Instant::now();
+
+        let corpus = state.corpus();
+        let mut inputs = Vec::new();
+        for id in corpus.ids() {
+            let mut testcase = corpus.get(id)?.borrow_mut();
+            if !is_found_by(&testcase, self.found_by) {
+                continue;
+            }
+            corpus.load_input_into(&mut testcase)?;
+            inputs.extend(testcase.input().clone());
+        }
+        write_seeds(&mut BufWriter::new(File::create(&self.path)?), &inputs)?;
+        Ok(())
+    }
+
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}
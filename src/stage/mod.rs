@@ -0,0 +1,2 @@
+pub mod dump_corpus;
+pub mod novelty;
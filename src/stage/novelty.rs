@@ -0,0 +1,84 @@
+use std::{
+    marker::PhantomData,
+    mem,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use libafl::{
+    events::EventFirer,
+    stages::Stage,
+    state::{State, UsesState},
+    Error, HasMetadata, HasNamedMetadata,
+};
+
+use crate::feedback::{store_states, PendingNoveltyMetadata};
+
+/// How often changed feedback state is saved.
+const STORE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sends novelty found by feedbacks to other clients and periodically saves feedback state.
+/// Runs after the mutational stage, so the testcases novelty came with reach other clients first
+/// and are still new to them.
+pub struct NoveltyStage<EM, Z> {
+    state_dir: Box<Path>,
+    last_store: Instant,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> NoveltyStage<EM, Z> {
+    pub fn new(state_dir: Box<Path>) -> Self {
+        Self {
+            state_dir,
+            last_store: Instant::now(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<EM, Z> UsesState for NoveltyStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for NoveltyStage<EM, Z>
+where
+    EM: EventFirer,
+    EM::State: State + HasMetadata + HasNamedMetadata,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let pending = state.metadata_or_insert_with(PendingNoveltyMetadata::default);
+        let novelty = mem::take(&mut pending.novelty);
+        let unsaved = pending.unsaved;
+        if !novelty.is_empty() {
+            novelty.fire(state, manager)?;
+        }
+        if unsaved && self.last_store.elapsed() >= STORE_INTERVAL {
+            store_states(state, &self.state_dir)?;
+            state
+                .metadata_or_insert_with(PendingNoveltyMetadata::default)
+                .unsaved = false;
+            self.last_store = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}